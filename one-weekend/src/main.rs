@@ -1,11 +1,17 @@
-use std::{fs, io, sync::Arc, time::Instant};
+use std::{io, path::Path, sync::Arc, time::Instant};
 use glam::DVec3;
-use itertools::{self, Itertools};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 use raytracer::{
+    bvh::Bvh,
     camera::CameraBuilder,
+    environment::Environment,
     hittable::HittableList,
+    integrator::NeePathTracer,
     material::Material,
+    progress::IndicatifProgress,
+    render::RenderSettings,
+    rng::RenderRng,
+    scene::Scene,
     sphere::Sphere, vector_utils
 };
 
@@ -22,16 +28,19 @@ fn main() -> io::Result<()> {
     let max_depth = 100;
 
     let output = "output/final_scene.ppm";
+    // Saved every few samples, so an interrupted render picks up where it stopped
+    let checkpoint = "output/final_scene.checkpoint";
+    let samples_per_checkpoint = 25;
 
     let mut world = HittableList::new();
 
     /*
     let material_ground = Material::Lambertian {
-        albedo: Color::new(0.8, 0.8, 0.0)
+        albedo: Color::new(0.8, 0.8, 0.0).into()
     };
 
     let material_center = Material::Lambertian {
-        albedo: Color::new(0.1, 0.2, 0.5)
+        albedo: Color::new(0.1, 0.2, 0.5).into()
     };
 
     let material_left = Material::Dielectric {
@@ -82,13 +91,14 @@ fn main() -> io::Result<()> {
 
     // Ground
     let ground = Material::Lambertian {
-        albedo: Color::new(0.5, 0.5, 0.5)
+        albedo: Color::new(0.5, 0.5, 0.5).into()
     };
     world.add(Box::new(Sphere::new(
         Point3::new(0.0, -1000.0, 0.0), 1000.0, ground)
     ));
 
-    let mut rng = rand::rng();
+    // Fixed seed so the scene layout is the same every run
+    let mut rng = RenderRng::seed_from_u64(42);
 
     for a in -11..11 {
         for b in -11..11 {
@@ -98,8 +108,8 @@ fn main() -> io::Result<()> {
             if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
                 let new_sphere = if choose_material < 0.8 {
                     // Lambertian
-                    let albedo = vector_utils::random_unit_vector() * vector_utils::random_unit_vector();
-                    Sphere::new(center, 0.2, Material::Lambertian { albedo })
+                    let albedo = vector_utils::random_unit_vector(&mut rng) * vector_utils::random_unit_vector(&mut rng);
+                    Sphere::new(center, 0.2, Material::Lambertian { albedo: albedo.into() })
                 } else if choose_material > 0.95 {
                     // Metal
                     let albedo = Color::new(
@@ -126,7 +136,7 @@ fn main() -> io::Result<()> {
         material1
     )));
 
-    let material2 = Material::Lambertian { albedo: Color::new(0.4, 0.2, 0.1) };
+    let material2 = Material::Lambertian { albedo: Color::new(0.4, 0.2, 0.1).into() };
     world.add(Box::new(Sphere::new(
         Point3::new(-4.0, 1.0, 0.0),
         1.0,
@@ -153,16 +163,18 @@ fn main() -> io::Result<()> {
         .build();
     
     println!("Rendering...");
-    let image = camera.render(Arc::new(world));
-
-    let preamble = format!("P3\n{} {}\n255\n", camera.image_width, camera.image_height);
-
-    let data = image.iter().map(|(r, g, b)| {
-        format!("{} {} {}", r, g, b)
-    })
-    .join("\n");
+    let scene = Scene::new(Arc::new(Bvh::new(world)), Environment::sky());
+    let settings = RenderSettings::new().progress(IndicatifProgress::new());
+    let rendered = match Path::new(checkpoint).exists() {
+        true => {
+            println!("Resuming from {}", checkpoint);
+            camera.resume(&scene, &NeePathTracer, &settings, checkpoint, samples_per_checkpoint)?
+        },
+        false => camera.render_checkpointed(&scene, &NeePathTracer, &settings, checkpoint, samples_per_checkpoint)?,
+    };
+    println!("{}", rendered.stats);
 
-    fs::write(output, format!("{}\n{}", preamble, data))?;
+    rendered.image.to_image().save(output)?;
 
     let elapsed = now.elapsed();
     println!("Finished render in {:.2?}", elapsed);
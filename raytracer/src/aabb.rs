@@ -0,0 +1,116 @@
+use std::ops::Range;
+use glam::DVec3;
+use crate::ray::Ray3;
+
+// Axis-aligned bounding box, stored as its minimum and maximum corners
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: DVec3,
+    pub max: DVec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+impl Aabb {
+    // Inverted box that contains nothing, so surrounding it with any box gives that box
+    pub const EMPTY: Aabb = Aabb {
+        min: DVec3::INFINITY,
+        max: DVec3::NEG_INFINITY,
+    };
+
+    // Build a box from two opposite corners, in any order
+    pub fn new(a: DVec3, b: DVec3) -> Aabb {
+        Aabb {
+            min: a.min(b),
+            max: a.max(b),
+        }.pad()
+    }
+
+    // Smallest box containing both boxes
+    pub fn surrounding(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    // Smallest box containing this box and a point
+    pub fn including(&self, point: DVec3) -> Aabb {
+        Aabb {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn centroid(&self) -> DVec3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn extent(&self) -> DVec3 {
+        self.max - self.min
+    }
+
+    // Index of the longest axis (0 = x, 1 = y, 2 = z)
+    pub fn longest_axis(&self) -> usize {
+        let extent = self.extent();
+        if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let e = self.extent();
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    pub fn hit(&self, ray: Ray3, interval: Range<f64>) -> bool {
+        self.hit_distance(ray.origin, ray.direction.recip(), interval).is_some()
+    }
+
+    // Slab test against a precomputed inverse direction, returning the entry distance.
+    // NaNs from 0 * inf are ignored by f64::max/min so flat boxes still behave.
+    pub(crate) fn hit_distance(&self, origin: DVec3, inverse_direction: DVec3, interval: Range<f64>) -> Option<f64> {
+        let t0 = (self.min - origin) * inverse_direction;
+        let t1 = (self.max - origin) * inverse_direction;
+
+        let t_near = t0.min(t1);
+        let t_far = t0.max(t1);
+
+        let t_enter = interval.start.max(t_near.x).max(t_near.y).max(t_near.z);
+        let t_exit = interval.end.min(t_far.x).min(t_far.y).min(t_far.z);
+
+        if t_enter <= t_exit {
+            Some(t_enter)
+        } else {
+            None
+        }
+    }
+
+    // Grow any axis thinner than a small delta, so planar primitives still have volume
+    fn pad(self) -> Aabb {
+        let delta = 1e-4;
+        let mut padded = self;
+        for axis in 0..3 {
+            if padded.max[axis] - padded.min[axis] < delta {
+                padded.min[axis] -= delta / 2.0;
+                padded.max[axis] += delta / 2.0;
+            }
+        }
+        padded
+    }
+}
@@ -0,0 +1,109 @@
+use glam::DVec3;
+use crate::{hittable::HitRecord, material::MaterialKind, ray::Ray3};
+
+// Extra per-pixel outputs (arbitrary output variables) rendered alongside the beauty image
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Aov {
+    // Material color at the first hit
+    Albedo,
+    // World space shading normal at the first hit
+    Normal,
+    // Distance to the nearest first hit among the pixel's samples, 0 where nothing was hit
+    // (the usual convention for background in compositing tools)
+    Depth,
+    // Light that bounced off exactly one surface on its way to the camera
+    Direct,
+    // Light that bounced off two or more surfaces
+    Indirect,
+    // Light seen directly: emission from the first surface hit, or the environment
+    Emission,
+    // Beauty image restricted to paths whose first hit has this kind of material. All materials
+    // of a kind share one layer, e.g. every Lambertian surface in the scene goes into "lambertian".
+    Material(MaterialKind),
+}
+
+impl Aov {
+    // Every AOV, for rendering them all at once
+    pub const ALL: [Aov; 10] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Emission,
+        Aov::Material(MaterialKind::Lambertian),
+        Aov::Material(MaterialKind::Metal),
+        Aov::Material(MaterialKind::Dielectric),
+        Aov::Material(MaterialKind::DiffuseLight),
+    ];
+
+    // Layer name used in output files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Emission => "emission",
+            Aov::Material(MaterialKind::Lambertian) => "lambertian",
+            Aov::Material(MaterialKind::Metal) => "metal",
+            Aov::Material(MaterialKind::Dielectric) => "dielectric",
+            Aov::Material(MaterialKind::DiffuseLight) => "light",
+        }
+    }
+}
+
+// AOV values for one camera ray, filled in by the integrator
+#[derive(Debug, Copy, Clone)]
+pub struct AovSample {
+    pub albedo: DVec3,
+    pub normal: DVec3,
+    pub depth: f64,
+    pub direct: DVec3,
+    pub indirect: DVec3,
+    pub emission: DVec3,
+    // Kind of material at the first hit, None if the ray escaped
+    pub material: Option<MaterialKind>,
+}
+
+impl Default for AovSample {
+    fn default() -> Self {
+        AovSample {
+            albedo: DVec3::ZERO,
+            normal: DVec3::ZERO,
+            depth: f64::INFINITY,
+            direct: DVec3::ZERO,
+            indirect: DVec3::ZERO,
+            emission: DVec3::ZERO,
+            material: None,
+        }
+    }
+}
+
+impl AovSample {
+    // Geometry and material of the first surface the camera ray hit
+    pub fn at_first_hit(ray: Ray3, record: &HitRecord) -> AovSample {
+        AovSample {
+            albedo: record.material.albedo(record),
+            normal: record.normal,
+            depth: record.t * ray.direction.length(),
+            material: Some(record.material.kind()),
+            ..AovSample::default()
+        }
+    }
+
+    // File light reaching the camera by the number of surfaces it bounced off on the way
+    pub fn add_light(&mut self, bounces: u32, light: DVec3) {
+        match bounces {
+            0 => self.emission += light,
+            1 => self.direct += light,
+            _ => self.indirect += light,
+        }
+    }
+
+    // The beauty value, which the light terms always add up to
+    pub fn radiance(&self) -> DVec3 {
+        self.emission + self.direct + self.indirect
+    }
+}
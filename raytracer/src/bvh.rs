@@ -0,0 +1,300 @@
+use std::ops::Range;
+use glam::{DVec2, DVec3};
+use crate::{
+    aabb::Aabb,
+    hittable::{self, HitRecord, Hittable, HittableList},
+    ray::Ray3,
+    stats,
+};
+
+// How the builder chooses where to divide a set of objects
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SplitMethod {
+    // Binned surface area heuristic, generally the fastest to traverse
+    #[default]
+    Sah,
+    // Split at the median centroid along the longest axis, faster to build
+    Median,
+}
+
+// Bounding volume hierarchy over a set of hittable objects.
+// Nodes are stored flattened in depth-first order, so the first child of an
+// interior node always directly follows its parent.
+pub struct Bvh<T: Hittable = Box<dyn Hittable>> {
+    nodes: Vec<BvhNode>,
+    objects: Vec<T>,
+    // Position of each object in the list the BVH was built from, reported as the hit's object ID
+    ids: Vec<u32>,
+}
+
+enum BvhNode {
+    Leaf {
+        bbox: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Interior {
+        bbox: Aabb,
+        second_child: usize,
+        axis: usize,
+    },
+}
+
+impl BvhNode {
+    fn bbox(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bbox, .. } => bbox,
+            BvhNode::Interior { bbox, .. } => bbox,
+        }
+    }
+}
+
+// Per-object data used only while building
+#[derive(Copy, Clone)]
+struct BuildEntry {
+    index: usize,
+    bbox: Aabb,
+    centroid: DVec3,
+}
+
+const MAX_OBJECTS_PER_LEAF: usize = 4;
+const SAH_BINS: usize = 12;
+const MAX_TRAVERSAL_DEPTH: usize = 64;
+
+impl Bvh {
+    pub fn new(list: HittableList) -> Self {
+        Self::with_split(list, SplitMethod::default())
+    }
+
+    pub fn with_split(list: HittableList, split: SplitMethod) -> Self {
+        Self::from_objects(list.into_objects(), split)
+    }
+}
+
+impl From<HittableList> for Bvh {
+    fn from(list: HittableList) -> Self {
+        Self::new(list)
+    }
+}
+
+impl<T: Hittable> Bvh<T> {
+    pub fn from_objects(objects: Vec<T>, split: SplitMethod) -> Self {
+        let mut entries = objects.iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bbox = object.bounding_box();
+                BuildEntry { index, bbox, centroid: bbox.centroid() }
+            })
+            .collect::<Vec<BuildEntry>>();
+
+        let mut nodes = Vec::with_capacity(2 * entries.len());
+        if !entries.is_empty() {
+            build_recursive(&mut entries, 0, split, &mut nodes, 0);
+        }
+
+        // Reorder objects so each leaf refers to a contiguous range
+        let mut slots = objects.into_iter().map(Some).collect::<Vec<Option<T>>>();
+        let objects = entries.iter()
+            .map(|entry| slots[entry.index].take().expect("object used twice in BVH"))
+            .collect::<Vec<T>>();
+        let ids = entries.iter()
+            .map(|entry| entry.index as u32)
+            .collect::<Vec<u32>>();
+
+        Bvh { nodes, objects, ids }
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+fn build_recursive(entries: &mut [BuildEntry], offset: usize, split: SplitMethod, nodes: &mut Vec<BvhNode>, depth: usize) {
+    let bbox = entries.iter().fold(Aabb::EMPTY, |acc, entry| acc.surrounding(&entry.bbox));
+    let centroid_bounds = entries.iter().fold(Aabb::EMPTY, |acc, entry| acc.including(entry.centroid));
+    let axis = centroid_bounds.longest_axis();
+
+    let count = entries.len();
+    let make_leaf = |nodes: &mut Vec<BvhNode>| nodes.push(BvhNode::Leaf {
+        bbox,
+        first: offset,
+        count,
+    });
+
+    // All centroids coincide (or there is nothing to split), so no split can separate them
+    let degenerate = centroid_bounds.max[axis] <= centroid_bounds.min[axis];
+    if count <= 1 || degenerate || depth >= MAX_TRAVERSAL_DEPTH - 1 {
+        make_leaf(nodes);
+        return;
+    }
+
+    let mid = match split {
+        SplitMethod::Median => Some(median_split(entries, axis)),
+        SplitMethod::Sah => sah_split(entries, axis, &bbox, &centroid_bounds),
+    };
+
+    let mid = match mid {
+        Some(mid) if mid > 0 && mid < count => mid,
+        // Splitting is not worth it
+        None => {
+            make_leaf(nodes);
+            return;
+        },
+        // Every object landed on one side of the plane, so fall back to the median
+        Some(_) => median_split(entries, axis),
+    };
+
+    let node_index = nodes.len();
+    nodes.push(BvhNode::Interior { bbox, second_child: 0, axis });
+
+    let (left, right) = entries.split_at_mut(mid);
+    build_recursive(left, offset, split, nodes, depth + 1);
+    let second = nodes.len();
+    build_recursive(right, offset + mid, split, nodes, depth + 1);
+
+    if let BvhNode::Interior { second_child, .. } = &mut nodes[node_index] {
+        *second_child = second;
+    }
+}
+
+fn median_split(entries: &mut [BuildEntry], axis: usize) -> usize {
+    let mid = entries.len() / 2;
+    entries.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    mid
+}
+
+// Bin centroids along the axis and pick the plane with the lowest estimated cost.
+// Returns None when a leaf is cheaper than any split.
+fn sah_split(entries: &mut [BuildEntry], axis: usize, bbox: &Aabb, centroid_bounds: &Aabb) -> Option<usize> {
+    let min = centroid_bounds.min[axis];
+    let extent = centroid_bounds.max[axis] - min;
+    let bin_of = |entry: &BuildEntry| {
+        let bin = ((entry.centroid[axis] - min) / extent * SAH_BINS as f64) as usize;
+        bin.min(SAH_BINS - 1)
+    };
+
+    let mut bin_counts = [0usize; SAH_BINS];
+    let mut bin_bounds = [Aabb::EMPTY; SAH_BINS];
+    for entry in entries.iter() {
+        let bin = bin_of(entry);
+        bin_counts[bin] += 1;
+        bin_bounds[bin] = bin_bounds[bin].surrounding(&entry.bbox);
+    }
+
+    // Sweep from the right to collect the cost of everything above each plane
+    let mut right_area = [0.0; SAH_BINS];
+    let mut right_count = [0usize; SAH_BINS];
+    let mut acc_bounds = Aabb::EMPTY;
+    let mut acc_count = 0;
+    for bin in (1..SAH_BINS).rev() {
+        acc_bounds = acc_bounds.surrounding(&bin_bounds[bin]);
+        acc_count += bin_counts[bin];
+        right_area[bin] = acc_bounds.surface_area();
+        right_count[bin] = acc_count;
+    }
+
+    // Then sweep from the left, splitting between bin - 1 and bin
+    let mut best_cost = f64::INFINITY;
+    let mut best_plane = 0;
+    let mut acc_bounds = Aabb::EMPTY;
+    let mut acc_count = 0;
+    for bin in 1..SAH_BINS {
+        acc_bounds = acc_bounds.surrounding(&bin_bounds[bin - 1]);
+        acc_count += bin_counts[bin - 1];
+        let cost = acc_count as f64 * acc_bounds.surface_area() + right_count[bin] as f64 * right_area[bin];
+        if cost < best_cost {
+            best_cost = cost;
+            best_plane = bin;
+        }
+    }
+
+    // Relative cost of one traversal step vs one intersection test is 1/8
+    let parent_area = bbox.surface_area().max(f64::MIN_POSITIVE);
+    let split_cost = 0.125 + best_cost / parent_area;
+    let leaf_cost = entries.len() as f64;
+    if entries.len() <= MAX_OBJECTS_PER_LEAF && leaf_cost <= split_cost {
+        return None;
+    }
+
+    let mut mid = 0;
+    for i in 0..entries.len() {
+        if bin_of(&entries[i]) < best_plane {
+            entries.swap(i, mid);
+            mid += 1;
+        }
+    }
+
+    Some(mid)
+}
+
+impl<T: Hittable> Hittable for Bvh<T> {
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse_direction = ray.direction.recip();
+        let direction_negative = [
+            ray.direction.x < 0.0,
+            ray.direction.y < 0.0,
+            ray.direction.z < 0.0,
+        ];
+
+        let mut closest_record: Option<HitRecord> = None;
+        let mut closest_t_so_far = interval.end;
+
+        let mut stack = [0usize; MAX_TRAVERSAL_DEPTH];
+        let mut stack_size = 1;
+
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node_index = stack[stack_size];
+            let node = &self.nodes[node_index];
+
+            stats::count_box_test();
+            if node.bbox().hit_distance(ray.origin, inverse_direction, interval.start..closest_t_so_far).is_none() {
+                continue;
+            }
+
+            match *node {
+                BvhNode::Leaf { first, count, .. } => {
+                    for (object, &id) in self.objects[first..first + count].iter().zip(&self.ids[first..first + count]) {
+                        if let Some(record) = object.hit(ray, interval.start..closest_t_so_far) {
+                            closest_t_so_far = record.t;
+                            closest_record = Some(record.with_object_id(id));
+                        }
+                    }
+                },
+                BvhNode::Interior { second_child, axis, .. } => {
+                    // Visit the nearer child first so later boxes can be culled by the closer hit
+                    let (near, far) = match direction_negative[axis] {
+                        true => (second_child, node_index + 1),
+                        false => (node_index + 1, second_child),
+                    };
+                    stack[stack_size] = far;
+                    stack[stack_size + 1] = near;
+                    stack_size += 2;
+                }
+            }
+        }
+
+        closest_record
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map(|node| *node.bbox()).unwrap_or(Aabb::EMPTY)
+    }
+
+    // Sampled like a HittableList, so a BVH of lights can go in Scene::lights
+    fn pdf_value(&self, origin: DVec3, direction: DVec3) -> f64 {
+        hittable::mixture_pdf_value(&self.objects, origin, direction)
+    }
+
+    fn random(&self, origin: DVec3, u: DVec2) -> DVec3 {
+        hittable::mixture_random(&self.objects, origin, u)
+    }
+}
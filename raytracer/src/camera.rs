@@ -1,98 +1,393 @@
-use std::sync::Arc;
+use std::{io, ops::ControlFlow, path::Path, sync::Arc};
 use rayon::prelude::*;
-use glam::DVec3;
-use indicatif::{ProgressBar, ProgressStyle};
-use rand::Rng;
+use glam::{DVec2, DVec3};
 use crate::{
-    hittable::Hittable,
+    aov::Aov,
+    checkpoint::{self, Sampling},
+    film::{Film, FilmPixel},
+    framebuffer::Framebuffer,
+    handle::{RenderControl, RenderHandle},
+    integrator::{Integrator, PathLimits},
     ray::Ray3,
+    render::{Pass, RenderOutput, RenderSettings},
+    sampler::{Sampler, SamplerKind},
+    scene::Scene,
+    stats::{self, PathStats, RunningStats},
+    tile,
     vector_utils,
 };
 
 #[derive(Default, Copy, Clone)]
 pub struct Camera {
+    // Size of the full frame, which sets the projection even when only a crop is rendered
     pub image_width: i32,
     pub image_height: i32,
+    // Part of the frame that gets rendered
+    pub crop: CropWindow,
     pixel_origin: DVec3,
     pixel_delta_u: DVec3,
     pixel_delta_v: DVec3,
+    // Most samples taken in any pixel
     pub samples_per_pixel: i32,
+    // Adaptive sampling: pixels stop once the standard error of their mean luminance, relative to
+    // the mean, drops below this. Zero takes every pixel to samples_per_pixel.
+    pub noise_threshold: f64,
+    // Samples every pixel takes before it may stop early
+    pub min_samples: i32,
     pub max_depth: i32,
+    // Surface hits before Russian roulette may end a path
+    pub roulette_depth: i32,
     pub location: DVec3,
+    // Unit vector the camera looks along
+    view_direction: DVec3,
     defocus_disk_u: DVec3,
     defocus_disk_v: DVec3,
+    pub sampler: SamplerKind,
 }
 
 impl Camera {
-    pub fn render(self, world: Arc<dyn Hittable>) -> Vec<(u32, u32, u32)> {
-        rayon::ThreadPoolBuilder::new().num_threads(6).build_global().unwrap();
-        let bar = ProgressBar::new(self.image_height as u64 * self.image_width as u64);
-        bar.set_style(ProgressStyle::default_bar());
-        // Generate iterator for all pixels
-        (0..self.image_height * self.image_width)
-            .into_par_iter()
-            .map(|index| {
-                // Extract (x, y) from iterator
-                let x = index % self.image_width;
-                let y = index / self.image_width;
-
-                let world = Arc::clone(&world);
-                let scale_factor = (self.samples_per_pixel as f64).recip();
-                
-                let multisampled_pixel_color = (0..self.samples_per_pixel)
-                    .map(|_| {
-                        // Get a ray, then get the color of that ray
-                        self.get_ray(x, y).color(self.max_depth, &*world)
-                    })
-                    // Sum all samples and scale
-                    .sum::<DVec3>() * scale_factor;
-
-                // Scale and clamp
-                let color = DVec3 {
-                    x: linear_to_gamma(multisampled_pixel_color.x),
-                    y: linear_to_gamma(multisampled_pixel_color.y),
-                    z: linear_to_gamma(multisampled_pixel_color.z),
-                }.clamp(DVec3::splat(0.0), DVec3::splat(0.999)) * 256.0;
-
-                bar.inc(1);
-
-                // Color tuple
-                (color.x as u32, color.y as u32, color.z as u32)
-            }).collect::<Vec<(u32, u32, u32)>>()
-    }
-
-    fn get_ray(&self, i: i32, j: i32) -> Ray3 {
-        let offset = sample_square();
+    // Render the scene into a framebuffer of linear radiance, computed by the integrator
+    pub fn render(self, scene: &Scene, integrator: &dyn Integrator, settings: &RenderSettings) -> Framebuffer {
+        self.render_output(scene, integrator, settings).image
+    }
+
+    // Render, also counting how long the traced paths were and how they ended
+    pub fn render_with_stats(self, scene: &Scene, integrator: &dyn Integrator, settings: &RenderSettings) -> (Framebuffer, PathStats) {
+        let output = self.render_output(scene, integrator, settings);
+        (output.image, output.stats)
+    }
+
+    // Render the beauty image together with the AOVs requested in the settings
+    pub fn render_output(self, scene: &Scene, integrator: &dyn Integrator, settings: &RenderSettings) -> RenderOutput {
+        let samples = self.samples_per_pixel as u32;
+        self.render_progressive(scene, integrator, settings, samples, |_| ControlFlow::Continue(()))
+    }
+
+    // Render in passes that each add up to `samples_per_pass` samples to every pixel. After each pass
+    // `on_pass` can look at everything accumulated so far through Pass::output, and can break to stop
+    // early. Returns the output of the last pass, which matches a one-shot render once all passes are done.
+    pub fn render_progressive(
+        self,
+        scene: &Scene,
+        integrator: &dyn Integrator,
+        settings: &RenderSettings,
+        samples_per_pass: u32,
+        on_pass: impl FnMut(&Pass) -> ControlFlow<()> + Send,
+    ) -> RenderOutput {
+        let control = RenderControl::new(self.sample_budget());
+        let job = RenderJob { scene, integrator, settings, control: &control };
+        self.render_controlled(self.new_film(settings), &job, samples_per_pass, on_pass)
+    }
+
+    // Render on a background thread. The handle reports progress and can pause or cancel the render;
+    // a cancelled render still hands back what it had so far.
+    pub fn render_in_background(self, scene: Arc<Scene>, integrator: Arc<dyn Integrator>, settings: RenderSettings) -> RenderHandle {
+        let control = Arc::new(RenderControl::new(self.sample_budget()));
+        let worker_control = control.clone();
+        let film = self.new_film(&settings);
+        RenderHandle::spawn(control, move || {
+            let job = RenderJob { scene: &scene, integrator: &*integrator, settings: &settings, control: &worker_control };
+            // One pass of every sample. Cancelling stops each pixel where it is, and the output is
+            // built from whatever had been taken.
+            let samples = self.samples_per_pixel as u32;
+            self.render_controlled(film, &job, samples, |_| ControlFlow::Continue(()))
+        })
+    }
+
+    // Render in passes of `samples_per_checkpoint` samples per pixel, saving everything accumulated
+    // to a checkpoint file at `path` after each pass. Stops with the error if a checkpoint can't be saved.
+    pub fn render_checkpointed(
+        self,
+        scene: &Scene,
+        integrator: &dyn Integrator,
+        settings: &RenderSettings,
+        path: impl AsRef<Path>,
+        samples_per_checkpoint: u32,
+    ) -> io::Result<RenderOutput> {
+        self.continue_checkpointed(self.new_film(settings), scene, integrator, settings, path.as_ref(), samples_per_checkpoint)
+    }
+
+    // Carry on from a checkpoint saved by render_checkpointed, with the same checkpointing. Pixels pick
+    // up their sample sequences where they left off, so an interrupted render ends up exactly as if it
+    // had run straight through. Raising samples_per_pixel (or lowering the noise threshold) adds samples
+    // to a render that had already finished, except with the stratified sampler, whose strata are laid
+    // out for the original sample count. The camera's image size, crop window and sampler, and the seed,
+    // AOVs and denoiser in the settings, must be the ones the checkpoint was made with.
+    pub fn resume(
+        self,
+        scene: &Scene,
+        integrator: &dyn Integrator,
+        settings: &RenderSettings,
+        path: impl AsRef<Path>,
+        samples_per_checkpoint: u32,
+    ) -> io::Result<RenderOutput> {
+        let path = path.as_ref();
+        let mut film = checkpoint::load(path, self.new_film(settings), self.sampling(settings))?;
+
+        // The sample budget or noise threshold may have changed since the checkpoint
+        for pixel in &mut film.pixels {
+            pixel.done = pixel.luminance.count() >= self.samples_per_pixel as u32 || self.converged(&pixel.luminance);
+        }
+
+        self.continue_checkpointed(film, scene, integrator, settings, path, samples_per_checkpoint)
+    }
+
+    fn continue_checkpointed(
+        self,
+        film: Film,
+        scene: &Scene,
+        integrator: &dyn Integrator,
+        settings: &RenderSettings,
+        path: &Path,
+        samples_per_checkpoint: u32,
+    ) -> io::Result<RenderOutput> {
+        let control = RenderControl::new(self.sample_budget());
+        // Samples already in the film count towards progress
+        let budget = self.samples_per_pixel as u32;
+        let taken = film.pixels.iter().map(|pixel| pixel.luminance.count().min(budget) as u64).sum();
+        control.add_work(taken, 0);
+
+        let job = RenderJob { scene, integrator, settings, control: &control };
+        let mut saved = Ok(());
+        let output = self.render_controlled(film, &job, samples_per_checkpoint, |pass| {
+            saved = checkpoint::save(path, pass.film, self.sampling(settings));
+            match saved.is_ok() {
+                true => ControlFlow::Continue(()),
+                false => ControlFlow::Break(()),
+            }
+        });
+        saved.map(|_| output)
+    }
+
+    fn sampling(&self, settings: &RenderSettings) -> Sampling {
+        Sampling {
+            sampler: self.sampler,
+            seed: settings.seed,
+            samples_per_pixel: self.samples_per_pixel as u32,
+        }
+    }
+
+    fn new_film(&self, settings: &RenderSettings) -> Film {
+        let frame = (self.image_width as usize, self.image_height as usize);
+        // The denoiser smooths across the crop's edges without seeing past them, so stitched crops
+        // would show seams
+        let full_frame = CropWindow { x: 0, y: 0, width: frame.0, height: frame.1 };
+        assert!(
+            settings.denoiser.is_none() || self.crop == full_frame,
+            "denoising a crop window gives seams when crops are stitched, denoise the full frame instead"
+        );
+        Film::new(self.crop, frame, settings.accumulated_aovs())
+    }
+
+    // Add passes to the film until every pixel is done, the render is cancelled or `on_pass` breaks.
+    // The output is only built once at the end, unless `on_pass` asks for it along the way.
+    fn render_controlled(
+        self,
+        mut film: Film,
+        job: &RenderJob,
+        samples_per_pass: u32,
+        mut on_pass: impl FnMut(&Pass) -> ControlFlow<()> + Send,
+    ) -> RenderOutput {
+        let RenderJob { settings, control, .. } = *job;
+        settings.install(|| {
+            settings.progress.update(control.samples_done(), control.samples_total);
+
+            let mut number = 0;
+            loop {
+                self.render_pass(&mut film, job, samples_per_pass.max(1));
+                number += 1;
+
+                let pass = Pass { number, film: &film, settings };
+                if control.is_cancelled() || on_pass(&pass).is_break() || film.is_finished() {
+                    break;
+                }
+            }
+
+            settings.progress.finish();
+            film.output(settings)
+        })
+    }
+
+    pub fn view_direction(&self) -> DVec3 {
+        self.view_direction
+    }
+
+    pub fn path_limits(&self) -> PathLimits {
+        PathLimits {
+            max_depth: self.max_depth,
+            roulette_depth: self.roulette_depth,
+        }
+    }
+
+    // Samples in the crop window if no pixel stops early
+    fn sample_budget(&self) -> u64 {
+        self.crop.width as u64 * self.crop.height as u64 * self.samples_per_pixel as u64
+    }
+
+    // Take up to `samples` more samples in every pixel that still wants them, tile by tile
+    fn render_pass(&self, film: &mut Film, job: &RenderJob, samples: u32) {
+        let RenderJob { settings, control, .. } = *job;
+        let size = settings.tile_size();
+        let tiles = tile::tiles(film.width, film.height, size, settings.tile_order);
+        let aovs = film.aovs.clone();
+        let buckets = film.tiles_mut(&tiles, size);
+
+        // Bridging keeps tiles starting in order, where splitting the list up would not
+        let stats = tiles.into_iter()
+            .zip(buckets)
+            .filter(|(_, pixels)| pixels.iter().any(|pixel| !pixel.done))
+            .par_bridge()
+            .map(|(tile, mut pixels)| {
+                let mut tile_stats = PathStats::new();
+                if control.is_cancelled() {
+                    return tile_stats;
+                }
+
+                let rays = stats::intersection_counts().rays;
+                let mut taken = 0;
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    // Frame coordinates from the position within the tile, so a crop takes the
+                    // same samples as the full frame would in those pixels
+                    let x = self.crop.x + tile.x + index % tile.width;
+                    let y = self.crop.y + tile.y + index / tile.width;
+                    if !pixel.done {
+                        taken += self.render_pixel(pixel, (x as u32, y as u32), &aovs, job, samples, &mut tile_stats);
+                    }
+                }
+
+                // Reported once per tile, as the reporter can be slow to call from every pixel
+                let done = control.add_work(taken, stats::intersection_counts().rays - rays);
+                settings.progress.update(done, control.samples_total);
+
+                settings.progress.tile_finished(tile, || {
+                    let image = pixels.iter().map(|pixel| pixel.mean()).collect();
+                    Framebuffer::from_pixels(tile.width, tile.height, image)
+                });
+                tile_stats
+            })
+            .reduce(PathStats::new, |mut stats, tile_stats| {
+                stats.merge(&tile_stats);
+                stats
+            });
+        film.stats.merge(&stats);
+    }
+
+    // Take up to `samples` more samples in one pixel, returning how many were taken
+    fn render_pixel(
+        &self,
+        pixel: &mut FilmPixel,
+        (x, y): (u32, u32),
+        aovs: &[Aov],
+        job: &RenderJob,
+        samples: u32,
+        stats: &mut PathStats,
+    ) -> u64 {
+        let RenderJob { scene, integrator, settings, control, .. } = *job;
+        let budget = self.samples_per_pixel as u32;
+
+        let mut sampler = self.sampler.create(settings.seed, budget);
+        let start = pixel.luminance.count();
+        let end = (start + samples).min(budget);
+
+        while pixel.luminance.count() < end {
+            control.wait_while_paused();
+            if control.is_cancelled() {
+                break;
+            }
+
+            // Sample indices carry on from earlier passes, so the sequence is the same however it is split
+            sampler.start_pixel_sample(x, y, pixel.luminance.count());
+            // Get a ray, then trace a path along it
+            let ray = self.get_ray(x as i32, y as i32, &mut *sampler);
+            let path = integrator.radiance(ray, scene, &mut *sampler, self.path_limits());
+            stats.record(path.length, path.end);
+            pixel.add(aovs, &path);
+
+            if self.converged(&pixel.luminance) {
+                pixel.done = true;
+                break;
+            }
+        }
+        if pixel.luminance.count() >= budget {
+            pixel.done = true;
+        }
+
+        (pixel.luminance.count() - start) as u64
+    }
+
+    // Whether a pixel's estimate is good enough to stop sampling it
+    fn converged(&self, luminance: &RunningStats) -> bool {
+        if self.noise_threshold <= 0.0 || luminance.count() < self.min_samples as u32 {
+            return false;
+        }
+
+        // Small floor so black pixels with no variance at all count as converged
+        luminance.standard_error() <= self.noise_threshold * luminance.mean().max(1e-3)
+    }
+
+    fn get_ray(&self, i: i32, j: i32, sampler: &mut dyn Sampler) -> Ray3 {
+        // Offset within [-0.5, 0.5] of the pixel center
+        let offset = sampler.get_2d() - 0.5;
+        let lens_sample = sampler.get_2d();
         let pixel_sample = self.pixel_origin
             + ((i as f64 + offset.x) * self.pixel_delta_u)
             + ((j as f64 + offset.y) * self.pixel_delta_v);
 
         let origin = match self.defocus_disk_u.length() <= 0.0 {
             true => self.location,
-            false => self.sample_defocus_disk(),
+            false => {
+                let offset = vector_utils::sample_unit_disk(lens_sample);
+                self.location + (offset.x * self.defocus_disk_u) + (offset.y * self.defocus_disk_v)
+            },
         };
         let direction = pixel_sample - origin;
         
         Ray3::new(origin, direction)
     }
 
-    fn sample_defocus_disk(&self) -> DVec3 {
-        let offset = vector_utils::random_in_unit_disk();
-        self.location + (offset.x * self.defocus_disk_u) + (offset.y * self.defocus_disk_v)
-    }
+}
+
+// Rectangle of pixels, from the top left of the frame
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CropWindow {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// Crop window as given to the builder, resolved against the image size on build
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Crop {
+    Pixels(CropWindow),
+    // Corners as fractions of the frame, (0, 0) top left to (1, 1) bottom right
+    Normalized { min: DVec2, max: DVec2 },
+}
+
+// Everything a render pass needs besides the film it adds to
+struct RenderJob<'a> {
+    scene: &'a Scene,
+    integrator: &'a dyn Integrator,
+    settings: &'a RenderSettings,
+    control: &'a RenderControl,
 }
 
 pub struct CameraBuilder {
     image_width: i32,
     image_height: i32,
     samples_per_pixel: i32,
+    noise_threshold: f64,
+    min_samples: i32,
     max_depth: i32,
+    roulette_depth: i32,
     vertical_fov: f64,
     relative_up: DVec3,
     position: DVec3,
     point_at: DVec3,
     focus_distance: f64,
     defocus_angle: f64,
+    sampler: SamplerKind,
+    crop: Option<Crop>,
 }
 
 impl Default for CameraBuilder {
@@ -108,13 +403,18 @@ impl CameraBuilder {
             image_width: 800,
             image_height: 400,
             samples_per_pixel: 10,
+            noise_threshold: 0.0,
+            min_samples: 16,
             max_depth: 10,
+            roulette_depth: 3,
             vertical_fov: 90.0,
             relative_up: DVec3 { x: 0.0, y: 1.0, z: 0.0 },
             position: DVec3 { x: 0.0, y: 0.0, z: 0.0 },
             point_at: DVec3 { x: 0.0, y: 0.0, z: -1.0 },
             focus_distance: 1.0,
             defocus_angle: 0.0,
+            sampler: SamplerKind::Independent,
+            crop: None,
         }
     }
 
@@ -123,7 +423,10 @@ impl CameraBuilder {
         let image_width = self.image_width.max(1);
         let image_height = self.image_height.max(1);
         let samples_per_pixel = self.samples_per_pixel.max(1);
+        let min_samples = self.min_samples.clamp(1, samples_per_pixel);
         let max_depth = self.max_depth.max(1);
+        let roulette_depth = self.roulette_depth.max(1);
+        let crop = self.crop_window(image_width as usize, image_height as usize);
 
         // Viewport dimensions
         let theta = self.vertical_fov.clamp(0.0, 180.0).to_radians();
@@ -154,14 +457,20 @@ impl CameraBuilder {
         Camera {
             image_width,
             image_height,
+            crop,
             pixel_origin,
             pixel_delta_u,
             pixel_delta_v,
             samples_per_pixel,
+            noise_threshold: self.noise_threshold.max(0.0),
+            min_samples,
             max_depth,
+            roulette_depth,
             location: self.position,
+            view_direction: -w,
             defocus_disk_u,
             defocus_disk_v,
+            sampler: self.sampler,
         }
     }
 
@@ -172,12 +481,48 @@ impl CameraBuilder {
         self
     }
 
+    // Only render this rectangle of pixels. The output is crop-sized, but the projection is the
+    // full frame's, so crops of the same frame line up when stitched together. Rendering a crop
+    // with a denoiser panics, as it would leave seams.
+    pub fn crop(mut self, x: usize, y: usize, width: usize, height: usize) -> Self {
+        self.crop = Some(Crop::Pixels(CropWindow { x, y, width, height }));
+        self
+    }
+
+    // Crop window from corners given as fractions of the frame, (0, 0) being the top left and
+    // (1, 1) the bottom right. Partly covered pixels are included.
+    pub fn crop_normalized(mut self, min: DVec2, max: DVec2) -> Self {
+        self.crop = Some(Crop::Normalized { min, max });
+        self
+    }
+
     pub fn pixel(mut self, samples: i32, depth: i32) -> Self {
         self.samples_per_pixel = samples;
         self.max_depth = depth;
         self
     }
 
+    // Turn on adaptive sampling: each pixel stops once its relative standard error falls below
+    // `threshold` (e.g. 0.01 for 1%), so samples_per_pixel becomes the budget for the noisiest pixels
+    pub fn noise_threshold(mut self, threshold: f64) -> Self {
+        self.noise_threshold = threshold;
+        self
+    }
+
+    // Samples every pixel takes before adaptive sampling may stop it, so that rare bright paths
+    // have a chance to show up in the variance estimate
+    pub fn min_samples(mut self, samples: i32) -> Self {
+        self.min_samples = samples;
+        self
+    }
+
+    // Let Russian roulette end paths once they have hit this many surfaces.
+    // Anything at or above the maximum depth turns roulette off.
+    pub fn russian_roulette(mut self, min_depth: i32) -> Self {
+        self.roulette_depth = min_depth;
+        self
+    }
+
     pub fn fov(mut self, vertical_fov: f64) -> Self {
         self.vertical_fov = vertical_fov;
         self
@@ -200,22 +545,139 @@ impl CameraBuilder {
         self.defocus_angle = defocus.clamp(0.0, 180.0);
         self
     }
-}
 
-fn sample_square() -> DVec3 {
-    let mut rng = rand::rng();
-    // rng.random() returns [0.0, 1.0] for f64
-    let x: f64 = rng.random();
-    let y: f64 = rng.random();
-    let z: f64 = rng.random();
-    // Final result should be within [-0.5, 0.5] in all dimensions
-    DVec3::new(x - 0.5, y - 0.5, z - 0.5)
+    pub fn sampler(mut self, sampler: SamplerKind) -> CameraBuilder {
+        self.sampler = sampler;
+        self
+    }
+
+    // Crop window in pixels, clipped to the frame and at least one pixel in size
+    fn crop_window(&self, width: usize, height: usize) -> CropWindow {
+        let (x0, y0, x1, y1) = match self.crop {
+            None => (0, 0, width, height),
+            Some(Crop::Pixels(window)) => (window.x, window.y, window.x + window.width, window.y + window.height),
+            Some(Crop::Normalized { min, max }) => {
+                let size = DVec2::new(width as f64, height as f64);
+                let min = (min.clamp(DVec2::ZERO, DVec2::ONE) * size).floor();
+                let max = (max.clamp(DVec2::ZERO, DVec2::ONE) * size).ceil();
+                (min.x as usize, min.y as usize, max.x as usize, max.y as usize)
+            },
+        };
+
+        let x = x0.min(width - 1);
+        let y = y0.min(height - 1);
+        CropWindow {
+            x,
+            y,
+            width: x1.min(width).saturating_sub(x).max(1),
+            height: y1.min(height).saturating_sub(y).max(1),
+        }
+    }
 }
 
-fn linear_to_gamma(scalar: f64) -> f64 {
-    if scalar > 0.0 {
-        scalar.sqrt()
-    } else {
-        0.0
+#[cfg(test)]
+mod tests {
+    use std::{ops::ControlFlow, sync::Arc};
+    use glam::DVec3;
+    use crate::{
+        denoise::Denoiser,
+        environment::Environment,
+        framebuffer::Framebuffer,
+        hittable::HittableList,
+        integrator::NeePathTracer,
+        material::Material,
+        render::{RenderSettings, Threads},
+        scene::Scene,
+        sphere::Sphere,
+    };
+    use super::CameraBuilder;
+
+    fn small_scene() -> Scene {
+        let mut world = HittableList::new();
+        let ground = Material::Lambertian { albedo: DVec3::new(0.5, 0.5, 0.5).into() };
+        let glass = Material::Dielectric { refraction_index: 1.5 };
+        let metal = Material::Metal { albedo: DVec3::new(0.8, 0.6, 0.2), fuzz: 0.3 };
+        world.add(Box::new(Sphere::new(DVec3::new(0.0, -100.5, -1.0), 100.0, ground)));
+        world.add(Box::new(Sphere::new(DVec3::new(-0.6, 0.0, -1.0), 0.5, glass)));
+        world.add(Box::new(Sphere::new(DVec3::new(0.6, 0.0, -1.0), 0.5, metal)));
+        Scene::new(Arc::new(world), Environment::sky())
+    }
+
+    #[test]
+    fn renders_are_identical_across_thread_counts() {
+        let scene = small_scene();
+        let camera = CameraBuilder::new().image(16, 12).pixel(4, 8).build();
+        let render = |threads| {
+            let settings = RenderSettings::new().seed(11).threads(threads).tiles(5, Default::default());
+            camera.render(&scene, &NeePathTracer, &settings)
+        };
+
+        let single = render(Threads::Count(1));
+        assert_eq!(single, render(Threads::Count(3)));
+        assert_eq!(single, render(Threads::Count(1)));
+    }
+
+    #[test]
+    fn stitched_crops_match_the_full_frame() {
+        let scene = small_scene();
+        let settings = RenderSettings::new().seed(5).tiles(4, Default::default());
+        let camera = |builder: CameraBuilder| builder.image(12, 8).pixel(3, 8).build();
+
+        let full = camera(CameraBuilder::new()).render(&scene, &NeePathTracer, &settings);
+        let left = camera(CameraBuilder::new().crop(0, 0, 7, 8)).render(&scene, &NeePathTracer, &settings);
+        let right = camera(CameraBuilder::new().crop(7, 0, 5, 8)).render(&scene, &NeePathTracer, &settings);
+
+        let mut stitched = Framebuffer::new(12, 8);
+        stitched.paste(0, 0, &left);
+        stitched.paste(7, 0, &right);
+        assert_eq!(stitched, full);
+    }
+
+    #[test]
+    #[should_panic(expected = "denoising a crop window")]
+    fn crops_cannot_be_denoised() {
+        let camera = CameraBuilder::new().image(12, 8).pixel(1, 2).crop(0, 0, 6, 8).build();
+        let settings = RenderSettings::new().denoise(Denoiser::new());
+        camera.render(&small_scene(), &NeePathTracer, &settings);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn progressive_renders_match_one_shot() {
+        let scene = small_scene();
+        let settings = RenderSettings::new().seed(3).tiles(4, Default::default());
+        for threshold in [0.0, 0.05] {
+            let camera = CameraBuilder::new().image(10, 6).pixel(7, 8).noise_threshold(threshold).min_samples(2).build();
+            let one_shot = camera.render_output(&scene, &NeePathTracer, &settings);
+            // Some pixels have to stop early for the threshold to be tested at all
+            assert_eq!(threshold > 0.0, one_shot.sample_counts.iter().any(|&count| count < 7));
+            for samples_per_pass in [1, 3] {
+                let progressive = camera.render_progressive(&scene, &NeePathTracer, &settings, samples_per_pass, |_| ControlFlow::Continue(()));
+                assert_eq!(progressive.image, one_shot.image);
+                assert_eq!(progressive.sample_counts, one_shot.sample_counts);
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_renders_return_what_they_have() {
+        let camera = CameraBuilder::new().image(16, 12).pixel(100_000, 8).build();
+        let handle = camera.render_in_background(Arc::new(small_scene()), Arc::new(NeePathTracer), RenderSettings::new());
+        handle.cancel();
+        let output = handle.wait();
+
+        assert_eq!(output.sample_counts.len(), 16 * 12);
+        let taken = output.sample_counts.iter().map(|&count| count as u64).sum::<u64>();
+        assert!(taken < 16 * 12 * 100_000);
+    }
+
+    #[test]
+    fn paused_renders_finish_when_waited_on() {
+        let camera = CameraBuilder::new().image(8, 6).pixel(4, 8).build();
+        let handle = camera.render_in_background(Arc::new(small_scene()), Arc::new(NeePathTracer), RenderSettings::new());
+        handle.pause();
+        assert!(handle.is_paused());
+        let output = handle.wait();
+
+        assert!(output.sample_counts.iter().all(|&count| count == 4));
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+use glam::DVec3;
+use crate::{
+    aov::Aov,
+    camera::CropWindow,
+    film::{Film, FilmPixel},
+    sampler::SamplerKind,
+    stats::{PathStats, RunningStats},
+};
+
+// Identifies the file format, bumped whenever the layout changes
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+
+// Stored by position, so new samplers go on the end
+const SAMPLERS: [SamplerKind; 5] = [
+    SamplerKind::Independent,
+    SamplerKind::Stratified,
+    SamplerKind::Halton,
+    SamplerKind::Sobol,
+    SamplerKind::BlueNoise,
+];
+
+// Far more path lengths than any render traces, to catch corrupt files before allocating
+const MAX_PATH_LENGTHS: u64 = 1 << 20;
+
+// How a film's samples were drawn, which a resumed render has to carry on with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Sampling {
+    pub(crate) sampler: SamplerKind,
+    pub(crate) seed: u64,
+    pub(crate) samples_per_pixel: u32,
+}
+
+// Write everything accumulated so far, so the render can be resumed later. The file is
+// replaced in one step, leaving the previous checkpoint intact if writing fails part way.
+pub(crate) fn save(path: &Path, film: &Film, sampling: Sampling) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+
+    let write = || {
+        let mut writer = BufWriter::new(File::create(&temporary)?);
+        write_checkpoint(&mut writer, film, sampling)?;
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&temporary, path)
+    };
+    write().map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+// Read a checkpoint into `film`, a fresh film set up for the render being resumed. Checkpoints
+// made for a different image, crop window, set of AOVs, sampler or seed are rejected before any
+// pixels are read, so the header of a corrupt file never decides how much gets allocated. So are
+// stratified ones with a different sample count, since the strata depend on it.
+pub(crate) fn load(path: &Path, film: Film, sampling: Sampling) -> io::Result<Film> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_checkpoint(&mut reader, film, sampling)
+        .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+}
+
+fn write_checkpoint(writer: &mut impl Write, film: &Film, sampling: Sampling) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    let sampler = SAMPLERS.iter().position(|known| *known == sampling.sampler).expect("every sampler is listed in SAMPLERS");
+    for value in [sampler as u64, sampling.seed, sampling.samples_per_pixel as u64] {
+        write_u64(writer, value)?;
+    }
+
+    for value in [film.crop.x, film.crop.y, film.crop.width, film.crop.height, film.frame.0, film.frame.1] {
+        write_u64(writer, value as u64)?;
+    }
+
+    write_u64(writer, film.aovs.len() as u64)?;
+    for aov in &film.aovs {
+        let index = Aov::ALL.iter().position(|known| known == aov).expect("every AOV is listed in Aov::ALL");
+        write_u64(writer, index as u64)?;
+    }
+
+    for pixel in &film.pixels {
+        write_vector(writer, pixel.sum)?;
+        let (count, mean, m2) = pixel.luminance.parts();
+        write_u64(writer, count as u64)?;
+        write_f64(writer, mean)?;
+        write_f64(writer, m2)?;
+        for sum in &pixel.aov_sums {
+            write_vector(writer, *sum)?;
+        }
+        writer.write_all(&[pixel.done as u8])?;
+    }
+
+    let stats = &film.stats;
+    write_u64(writer, stats.lengths.len() as u64)?;
+    for count in stats.lengths.iter().chain([&stats.escaped, &stats.absorbed, &stats.roulette, &stats.max_depth]) {
+        write_u64(writer, *count)?;
+    }
+    Ok(())
+}
+
+fn read_checkpoint(reader: &mut impl Read, mut film: Film, sampling: Sampling) -> io::Result<Film> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a render checkpoint"));
+    }
+
+    let sampler = SAMPLERS.get(read_u64(reader)? as usize).copied();
+    if sampler != Some(sampling.sampler) {
+        return Err(mismatch("sampler"));
+    }
+    if read_u64(reader)? != sampling.seed {
+        return Err(mismatch("seed"));
+    }
+    let samples_per_pixel = read_u64(reader)?;
+    if sampling.sampler == SamplerKind::Stratified && samples_per_pixel != sampling.samples_per_pixel as u64 {
+        return Err(mismatch("sample count, which the stratified sampler can't change"));
+    }
+
+    let [x, y, width, height, frame_width, frame_height] = [(); 6].map(|_| read_u64(reader).map(|value| value as usize));
+    let crop = CropWindow { x: x?, y: y?, width: width?, height: height? };
+    if (frame_width?, frame_height?) != film.frame {
+        return Err(mismatch("image size"));
+    }
+    if crop != film.crop {
+        return Err(mismatch("crop window"));
+    }
+
+    let aov_count = read_u64(reader)?;
+    if aov_count != film.aovs.len() as u64 {
+        return Err(mismatch("set of AOVs"));
+    }
+    for aov in &film.aovs {
+        let index = read_u64(reader)? as usize;
+        if Aov::ALL.get(index) != Some(aov) {
+            return Err(mismatch("set of AOVs"));
+        }
+    }
+
+    for pixel in &mut film.pixels {
+        *pixel = read_pixel(reader, pixel.aov_sums.len())?;
+    }
+
+    let length_count = read_u64(reader)?;
+    if length_count > MAX_PATH_LENGTHS {
+        return Err(invalid_data("path statistics out of range"));
+    }
+    let lengths = (0..length_count)
+        .map(|_| read_u64(reader))
+        .collect::<io::Result<Vec<u64>>>()?;
+    film.stats = PathStats {
+        lengths,
+        escaped: read_u64(reader)?,
+        absorbed: read_u64(reader)?,
+        roulette: read_u64(reader)?,
+        max_depth: read_u64(reader)?,
+    };
+
+    Ok(film)
+}
+
+fn read_pixel(reader: &mut impl Read, aov_count: usize) -> io::Result<FilmPixel> {
+    let sum = read_vector(reader)?;
+    let count = u32::try_from(read_u64(reader)?).map_err(|_| invalid_data("sample count out of range"))?;
+    let luminance = RunningStats::from_parts(count, read_f64(reader)?, read_f64(reader)?);
+    let aov_sums = (0..aov_count)
+        .map(|_| read_vector(reader))
+        .collect::<io::Result<Vec<DVec3>>>()?;
+    let mut done = [0];
+    reader.read_exact(&mut done)?;
+
+    Ok(FilmPixel {
+        sum,
+        luminance,
+        aov_sums,
+        done: done[0] != 0,
+    })
+}
+
+fn mismatch(setting: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("checkpoint was made with a different {}", setting))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_f64(writer: &mut impl Write, value: f64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_vector(writer: &mut impl Write, vector: DVec3) -> io::Result<()> {
+    for component in vector.to_array() {
+        write_f64(writer, component)?;
+    }
+    Ok(())
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+fn read_vector(reader: &mut impl Read) -> io::Result<DVec3> {
+    Ok(DVec3::new(read_f64(reader)?, read_f64(reader)?, read_f64(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io};
+    use glam::DVec3;
+    use crate::{aov::Aov, camera::CropWindow, film::Film, integrator::PathSample, sampler::SamplerKind, stats::PathEnd};
+    use super::{load, read_checkpoint, save, write_checkpoint, Sampling};
+
+    const SAMPLING: Sampling = Sampling { sampler: SamplerKind::Sobol, seed: 7, samples_per_pixel: 16 };
+
+    fn crop() -> CropWindow {
+        CropWindow { x: 2, y: 1, width: 3, height: 2 }
+    }
+
+    fn film() -> Film {
+        Film::new(crop(), (8, 4), vec![Aov::Depth, Aov::Albedo])
+    }
+
+    fn rendered_film() -> Film {
+        let mut film = film();
+        let aovs = film.aovs.clone();
+        for (index, pixel) in film.pixels.iter_mut().enumerate() {
+            for sample in 0..=index {
+                let mut path = PathSample {
+                    radiance: DVec3::new(index as f64, sample as f64 * 0.1, 0.25),
+                    length: sample as u32,
+                    end: PathEnd::Escaped,
+                    aov: Default::default(),
+                };
+                path.aov.albedo = DVec3::splat(0.5);
+                path.aov.depth = 1.0 + sample as f64;
+                pixel.add(&aovs, &path);
+                film.stats.record(path.length, path.end);
+            }
+        }
+        film.pixels[3].done = true;
+        film
+    }
+
+    #[test]
+    fn checkpoint_round_trip() {
+        let film = rendered_film();
+        let mut bytes = Vec::new();
+        write_checkpoint(&mut bytes, &film, SAMPLING).unwrap();
+
+        let loaded = read_checkpoint(&mut bytes.as_slice(), self::film(), SAMPLING).unwrap();
+        assert_eq!(loaded.pixels, film.pixels);
+        assert_eq!(loaded.stats, film.stats);
+    }
+
+    #[test]
+    fn checkpoint_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("raytracer-checkpoint-{}", std::process::id()));
+        let film = rendered_film();
+        save(&path, &film, SAMPLING).unwrap();
+        let loaded = load(&path, self::film(), SAMPLING);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap().pixels, film.pixels);
+    }
+
+    #[test]
+    fn checkpoint_rejects_other_renders() {
+        let mut bytes = Vec::new();
+        write_checkpoint(&mut bytes, &rendered_film(), SAMPLING).unwrap();
+
+        let other_crop = Film::new(CropWindow { x: 0, ..crop() }, (8, 4), vec![Aov::Depth, Aov::Albedo]);
+        let error = read_checkpoint(&mut bytes.as_slice(), other_crop, SAMPLING).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+        let other_aovs = Film::new(crop(), (8, 4), vec![Aov::Albedo, Aov::Depth]);
+        let error = read_checkpoint(&mut bytes.as_slice(), other_aovs, SAMPLING).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let other_seed = Sampling { seed: 8, ..SAMPLING };
+        let error = read_checkpoint(&mut bytes.as_slice(), film(), other_seed).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn checkpoint_sample_count_can_change_unless_stratified() {
+        let more_samples = Sampling { samples_per_pixel: 64, ..SAMPLING };
+        let mut bytes = Vec::new();
+        write_checkpoint(&mut bytes, &rendered_film(), SAMPLING).unwrap();
+        assert!(read_checkpoint(&mut bytes.as_slice(), film(), more_samples).is_ok());
+
+        let stratified = Sampling { sampler: SamplerKind::Stratified, ..SAMPLING };
+        let mut bytes = Vec::new();
+        write_checkpoint(&mut bytes, &rendered_film(), stratified).unwrap();
+        assert!(read_checkpoint(&mut bytes.as_slice(), film(), stratified).is_ok());
+        let error = read_checkpoint(&mut bytes.as_slice(), film(), Sampling { samples_per_pixel: 64, ..stratified }).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn checkpoint_rejects_corrupt_files() {
+        let mut bytes = Vec::new();
+        write_checkpoint(&mut bytes, &rendered_film(), SAMPLING).unwrap();
+
+        let error = read_checkpoint(&mut &bytes[..bytes.len() / 2], film(), SAMPLING).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        bytes[0] = b'X';
+        let error = read_checkpoint(&mut bytes.as_slice(), film(), SAMPLING).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,77 +1,41 @@
-use std::ops;
+use glam::DVec3;
 
-use crate::vector::Vector3;
+// Conversions between linear light and encoded display values
 
-#[derive(Debug, Default, Copy, Clone)]
-pub struct ColorRGB {
-    pub r: f64,
-    pub g: f64,
-    pub b: f64
+// Relative luminance of linear Rec. 709 / sRGB primaries
+pub fn luminance(color: DVec3) -> f64 {
+    color.dot(DVec3::new(0.2126, 0.7152, 0.0722))
 }
 
-impl ColorRGB {
-    pub fn new(r: f64, g: f64, b: f64) -> ColorRGB {
-        ColorRGB {
-            r: r.clamp(0.0, 1.0),
-            g: g.clamp(0.0, 1.0),
-            b: b.clamp(0.0, 1.0)}
+// sRGB transfer function (OETF), linear [0, 1] to encoded [0, 1]
+pub fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        12.92 * value.max(0.0)
+    } else {
+        1.055 * value.powf(2.4_f64.recip()) - 0.055
     }
 }
 
-impl ops::Add for ColorRGB {
-    type Output = ColorRGB;
-
-    fn add(self, other: ColorRGB) -> ColorRGB {
-        ColorRGB {
-            r: (self.r + other.r).clamp(0.0, 1.0),
-            g: (self.g + other.g).clamp(0.0, 1.0),
-            b: (self.b + other.b).clamp(0.0, 1.0),
-        }
-    }
-}
-
-impl ops::Add<ColorRGB> for Vector3 {
-    type Output = ColorRGB;
-
-    fn add(self, other: ColorRGB) -> ColorRGB {
-        ColorRGB {
-            r: (self.x + other.r).clamp(0.0, 1.0),
-            g: (self.y + other.g).clamp(0.0, 1.0),
-            b: (self.z + other.b).clamp(0.0, 1.0),
-        }
+// Inverse sRGB transfer function, encoded [0, 1] to linear [0, 1]
+pub fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
     }
 }
 
-impl ops::Mul<f64> for ColorRGB {
-    type Output = ColorRGB;
-
-    fn mul(self, other: f64) -> ColorRGB {
-        ColorRGB {
-            r: (self.r * other).clamp(0.0, 1.0),
-            g: (self.g * other).clamp(0.0, 1.0),
-            b: (self.b * other).clamp(0.0, 1.0),
-        }
-    }
+// Blue through cyan, green and yellow to red as `value` goes from 0 to 1
+pub fn heatmap(value: f64) -> DVec3 {
+    const STOPS: [DVec3; 5] = [
+        DVec3::new(0.0, 0.0, 1.0),
+        DVec3::new(0.0, 1.0, 1.0),
+        DVec3::new(0.0, 1.0, 0.0),
+        DVec3::new(1.0, 1.0, 0.0),
+        DVec3::new(1.0, 0.0, 0.0),
+    ];
+
+    let position = value.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let index = (position as usize).min(STOPS.len() - 2);
+    STOPS[index].lerp(STOPS[index + 1], position - index as f64)
 }
-
-impl ops::Mul<ColorRGB> for f64 {
-    type Output = ColorRGB;
-
-    fn mul(self, other: ColorRGB) -> ColorRGB {
-        ColorRGB::mul(other, self)
-    }
-}
-
-pub fn ppm_preamble(x: i32, y: i32) {
-    println!("P3");
-    println!("{} {}", x, y);
-    println!("255");
-}
-
-pub fn ppm_write_pixel(color: ColorRGB) {
-    let r = (color.r * 256.0) as u8;
-    let g = (color.g * 256.0) as u8;
-    let b = (color.b * 256.0) as u8;
-
-    println!("{} {} {}", r, g, b);
-}
\ No newline at end of file
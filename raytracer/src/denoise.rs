@@ -0,0 +1,156 @@
+use glam::DVec3;
+use rayon::prelude::*;
+use crate::{color, framebuffer::Framebuffer};
+
+// B3 spline weights for offsets -2..=2
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// Taps of a pass this far along are already millions of pixels apart
+pub const MAX_ITERATIONS: u32 = 24;
+
+// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010).
+// Each pass blurs with a 5x5 kernel whose taps are spread twice as far apart as the last pass's,
+// and every tap is weighted down where color, albedo or normal differ from the center pixel,
+// so noise is smoothed away without blurring across edges and texture detail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Denoiser {
+    iterations: u32,
+    color_sigma: f64,
+    albedo_sigma: f64,
+    normal_sigma: f64,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            color_sigma: 0.8,
+            albedo_sigma: 0.1,
+            normal_sigma: 0.3,
+        }
+    }
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Number of passes, the filter reaches 2^(iterations + 1) pixels either way.
+    // At most MAX_ITERATIONS, far beyond the point where more passes change anything.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations.min(MAX_ITERATIONS);
+        self
+    }
+
+    // Edge-stopping widths: larger values smooth more across differences in each buffer
+    pub fn color_sigma(mut self, sigma: f64) -> Self {
+        self.color_sigma = sigma;
+        self
+    }
+
+    pub fn albedo_sigma(mut self, sigma: f64) -> Self {
+        self.albedo_sigma = sigma;
+        self
+    }
+
+    pub fn normal_sigma(mut self, sigma: f64) -> Self {
+        self.normal_sigma = sigma;
+        self
+    }
+
+    // Filter a noisy image using albedo and normal buffers of the same size, such as the
+    // Aov::Albedo and Aov::Normal layers of the same render
+    pub fn apply(&self, color: &Framebuffer, albedo: &Framebuffer, normal: &Framebuffer) -> Framebuffer {
+        let (width, height) = (color.width(), color.height());
+        assert!(
+            albedo.width() == width && albedo.height() == height && normal.width() == width && normal.height() == height,
+            "denoiser feature buffers must match the image size"
+        );
+
+        // Filter illumination rather than color, so texture detail is put back unblurred afterwards
+        let mut illumination = color.pixels().iter()
+            .zip(albedo.pixels())
+            .map(|(color, albedo)| demodulate(*color, *albedo))
+            .collect::<Vec<DVec3>>();
+
+        for iteration in 0..self.iterations {
+            // Holes wider than the image would only sample its edges
+            let step = (1isize << iteration).min(width.max(height) as isize);
+            // Later passes see smoother input, so tighten the color test as in the paper
+            let color_sigma = self.color_sigma / (1u64 << iteration) as f64;
+            let input = Framebuffer::from_pixels(width, height, illumination);
+
+            illumination = (0..width * height)
+                .into_par_iter()
+                .map(|index| self.filter_pixel(index % width, index / width, step, color_sigma, &input, albedo, normal))
+                .collect::<Vec<DVec3>>();
+        }
+
+        let pixels = illumination.iter()
+            .zip(albedo.pixels())
+            .map(|(illumination, albedo)| *illumination * modulation(*albedo))
+            .collect::<Vec<DVec3>>();
+
+        Framebuffer::from_pixels(width, height, pixels)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn filter_pixel(&self, x: usize, y: usize, step: isize, color_sigma: f64, input: &Framebuffer, albedo: &Framebuffer, normal: &Framebuffer) -> DVec3 {
+        let center_color = compress(input.get(x, y));
+        let center_albedo = albedo.get(x, y);
+        let center_normal = normal.get(x, y);
+
+        let mut sum = DVec3::ZERO;
+        let mut total_weight = 0.0;
+
+        for (j, kernel_y) in KERNEL.iter().enumerate() {
+            let sample_y = y as isize + (j as isize - 2) * step;
+            if sample_y < 0 || sample_y >= input.height() as isize {
+                continue;
+            }
+
+            for (i, kernel_x) in KERNEL.iter().enumerate() {
+                let sample_x = x as isize + (i as isize - 2) * step;
+                if sample_x < 0 || sample_x >= input.width() as isize {
+                    continue;
+                }
+                let (sample_x, sample_y) = (sample_x as usize, sample_y as usize);
+
+                let sample = input.get(sample_x, sample_y);
+                let weight = kernel_x * kernel_y
+                    * edge_weight(center_color, compress(sample), color_sigma)
+                    * edge_weight(center_albedo, albedo.get(sample_x, sample_y), self.albedo_sigma)
+                    * edge_weight(center_normal, normal.get(sample_x, sample_y), self.normal_sigma);
+
+                sum += weight * sample;
+                total_weight += weight;
+            }
+        }
+
+        // The center tap always has weight, so this only guards against underflow
+        match total_weight > 0.0 {
+            true => sum / total_weight,
+            false => input.get(x, y),
+        }
+    }
+}
+
+fn edge_weight(center: DVec3, sample: DVec3, sigma: f64) -> f64 {
+    let sigma_squared = (sigma * sigma).max(f64::MIN_POSITIVE);
+    (-(center - sample).length_squared() / sigma_squared).exp()
+}
+
+// Bring HDR values into [0, 1) before comparing them, so bright lights don't stop all filtering
+fn compress(color: DVec3) -> DVec3 {
+    color / (1.0 + color::luminance(color.max(DVec3::ZERO)))
+}
+
+// Albedo to divide out, with black or missing albedo left alone
+fn modulation(albedo: DVec3) -> DVec3 {
+    DVec3::select(albedo.cmpgt(DVec3::splat(0.01)), albedo, DVec3::ONE)
+}
+
+fn demodulate(color: DVec3, albedo: DVec3) -> DVec3 {
+    color / modulation(albedo)
+}
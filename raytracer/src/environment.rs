@@ -0,0 +1,127 @@
+use std::{f64::consts::PI, io, path::Path, sync::Arc};
+use glam::{DVec2, DVec3};
+
+// What a ray sees when it escapes the scene
+#[derive(Clone)]
+pub enum Environment {
+    Solid(DVec3),
+    // Vertical blend from the color looking straight down to the color looking straight up
+    Gradient {
+        bottom: DVec3,
+        top: DVec3,
+    },
+    // Equirectangular (latitude-longitude) image surrounding the scene
+    Map(Arc<EnvironmentMap>),
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::sky()
+    }
+}
+
+impl Environment {
+    // The blue-white sky from the book
+    pub fn sky() -> Self {
+        Environment::Gradient {
+            bottom: DVec3::new(1.0, 1.0, 1.0),
+            top: DVec3::new(0.5, 0.7, 1.0),
+        }
+    }
+
+    pub fn color(&self, direction: DVec3) -> DVec3 {
+        match self {
+            Environment::Solid(color) => *color,
+            Environment::Gradient { bottom, top } => {
+                let unit_dir = direction.normalize();
+                let a = 0.5 * (unit_dir.y + 1.0);
+                (1.0 - a) * *bottom + a * *top
+            },
+            Environment::Map(map) => map.sample(direction),
+        }
+    }
+}
+
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    // Linear radiance, top row first
+    pixels: Vec<DVec3>,
+    // Rotation about the y axis, in radians
+    rotation: f64,
+    intensity: f64,
+}
+
+impl EnvironmentMap {
+    // Load a Radiance .hdr or OpenEXR file (or any format the image crate reads, treated as linear)
+    pub fn load(path: impl AsRef<Path>) -> io::Result<EnvironmentMap> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?
+            .into_rgb32f();
+
+        let pixels = image.pixels()
+            .map(|pixel| DVec3::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64))
+            .collect::<Vec<DVec3>>();
+
+        Ok(Self::from_pixels(image.width() as usize, image.height() as usize, pixels))
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<DVec3>) -> EnvironmentMap {
+        assert_eq!(pixels.len(), width * height, "environment map needs width * height pixels");
+        EnvironmentMap {
+            width,
+            height,
+            pixels,
+            rotation: 0.0,
+            intensity: 1.0,
+        }
+    }
+
+    // Spin the map about the vertical axis, in degrees
+    pub fn with_rotation(mut self, degrees: f64) -> Self {
+        self.rotation = degrees.to_radians();
+        self
+    }
+
+    // Scale factor applied to every lookup
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn sample(&self, direction: DVec3) -> DVec3 {
+        if self.pixels.is_empty() {
+            return DVec3::ZERO;
+        }
+
+        let uv = direction_to_uv(direction, self.rotation);
+        let x = uv.x * self.width as f64 - 0.5;
+        let y = uv.y * self.height as f64 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        // Wrap around horizontally, clamp at the poles
+        let texel = |i: f64, j: f64| {
+            let i = (i as i64).rem_euclid(self.width as i64) as usize;
+            let j = (j as i64).clamp(0, self.height as i64 - 1) as usize;
+            self.pixels[j * self.width + i]
+        };
+
+        let upper = texel(x0, y0).lerp(texel(x0 + 1.0, y0), tx);
+        let lower = texel(x0, y0 + 1.0).lerp(texel(x0 + 1.0, y0 + 1.0), tx);
+        self.intensity * upper.lerp(lower, ty)
+    }
+}
+
+// Map a direction to equirectangular image coordinates, (0, 0) being the top left.
+// The center of the image looks down -z.
+fn direction_to_uv(direction: DVec3, rotation: f64) -> DVec2 {
+    let unit_dir = direction.normalize();
+    let phi = unit_dir.x.atan2(-unit_dir.z) - rotation;
+    let theta = unit_dir.y.clamp(-1.0, 1.0).acos();
+    DVec2::new((phi / (2.0 * PI) + 0.5).rem_euclid(1.0), theta / PI)
+}
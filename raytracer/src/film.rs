@@ -0,0 +1,195 @@
+use glam::DVec3;
+use crate::{
+    aov::{Aov, AovSample},
+    camera::CropWindow,
+    color,
+    framebuffer::Framebuffer,
+    integrator::PathSample,
+    render::{RenderOutput, RenderSettings},
+    stats::{PathStats, RunningStats},
+    tile::Tile,
+};
+
+// Running totals for every pixel of a render in progress, from which the output can be
+// built at any point
+pub(crate) struct Film {
+    // Size of the crop window being rendered
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) crop: CropWindow,
+    // Size of the camera's full frame
+    pub(crate) frame: (usize, usize),
+    // AOVs being accumulated, the requested ones followed by any the denoiser needs
+    pub(crate) aovs: Vec<Aov>,
+    pub(crate) pixels: Vec<FilmPixel>,
+    // Lengths and endings of every path traced into the film
+    pub(crate) stats: PathStats,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FilmPixel {
+    pub(crate) sum: DVec3,
+    // Luminance of every sample, for adaptive sampling
+    pub(crate) luminance: RunningStats,
+    // One total per AOV, in the film's order
+    pub(crate) aov_sums: Vec<DVec3>,
+    // Budget used up or converged, so no more samples are wanted
+    pub(crate) done: bool,
+}
+
+impl Film {
+    pub(crate) fn new(crop: CropWindow, frame: (usize, usize), aovs: Vec<Aov>) -> Film {
+        let aov_sums = aovs.iter()
+            .map(|aov| match aov {
+                // Depth keeps the nearest hit rather than a sum
+                Aov::Depth => DVec3::INFINITY,
+                _ => DVec3::ZERO,
+            })
+            .collect::<Vec<DVec3>>();
+        let pixel = FilmPixel {
+            sum: DVec3::ZERO,
+            luminance: RunningStats::new(),
+            aov_sums,
+            done: false,
+        };
+
+        Film {
+            width: crop.width,
+            height: crop.height,
+            crop,
+            frame,
+            aovs,
+            pixels: vec![pixel; crop.width * crop.height],
+            stats: PathStats::new(),
+        }
+    }
+
+    // Split the pixels up between tiles from tile::tiles, each tile's in row order, so tiles
+    // can be rendered in parallel
+    pub(crate) fn tiles_mut(&mut self, tiles: &[Tile], size: usize) -> Vec<Vec<&mut FilmPixel>> {
+        let columns = self.width.div_ceil(size);
+        // Position in `tiles` of each cell of the tile grid
+        let mut slots = vec![0; tiles.len()];
+        for (slot, tile) in tiles.iter().enumerate() {
+            slots[tile.y / size * columns + tile.x / size] = slot;
+        }
+
+        let mut buckets = tiles.iter()
+            .map(|tile| Vec::with_capacity(tile.width * tile.height))
+            .collect::<Vec<Vec<&mut FilmPixel>>>();
+        for (index, pixel) in self.pixels.iter_mut().enumerate() {
+            let (x, y) = (index % self.width, index / self.width);
+            buckets[slots[y / size * columns + x / size]].push(pixel);
+        }
+        buckets
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.pixels.iter().all(|pixel| pixel.done)
+    }
+
+    // Average everything gathered so far. Pixels without samples are black.
+    pub(crate) fn output(&self, settings: &RenderSettings) -> RenderOutput {
+        let image = self.pixels.iter()
+            .map(FilmPixel::mean)
+            .collect::<Vec<DVec3>>();
+        let mut image = Framebuffer::from_pixels(self.width, self.height, image);
+
+        let mut aovs = self.aovs.iter()
+            .enumerate()
+            .map(|(layer, aov)| {
+                let layer_pixels = self.pixels.iter()
+                    .map(|pixel| match aov {
+                        // Misses stay infinite while accumulating, so any hit replaces them
+                        Aov::Depth => match pixel.aov_sums[layer].is_finite() {
+                            true => pixel.aov_sums[layer],
+                            false => DVec3::ZERO,
+                        },
+                        _ => pixel.aov_sums[layer] / pixel.luminance.count().max(1) as f64,
+                    })
+                    .collect::<Vec<DVec3>>();
+                (*aov, Framebuffer::from_pixels(self.width, self.height, layer_pixels))
+            })
+            .collect::<Vec<(Aov, Framebuffer)>>();
+
+        if let Some(denoiser) = &settings.denoiser {
+            let feature = |aov: Aov| &aovs.iter().find(|(layer, _)| *layer == aov).expect("denoiser feature buffer").1;
+            image = denoiser.apply(&image, feature(Aov::Albedo), feature(Aov::Normal));
+            // Drop feature buffers that were only gathered for the denoiser
+            aovs.truncate(settings.aovs.len());
+        }
+
+        RenderOutput {
+            image,
+            aovs,
+            sample_counts: self.pixels.iter().map(|pixel| pixel.luminance.count()).collect(),
+            stats: self.stats.clone(),
+            crop: self.crop,
+            frame_width: self.frame.0,
+            frame_height: self.frame.1,
+        }
+    }
+}
+
+impl FilmPixel {
+    // Current estimate of the pixel's color
+    pub(crate) fn mean(&self) -> DVec3 {
+        self.sum / self.luminance.count().max(1) as f64
+    }
+
+    pub(crate) fn add(&mut self, aovs: &[Aov], path: &PathSample) {
+        self.sum += path.radiance;
+        self.luminance.add(color::luminance(path.radiance));
+        add_aovs(aovs, &mut self.aov_sums, &path.aov, path.radiance);
+    }
+}
+
+fn add_aovs(aovs: &[Aov], sums: &mut [DVec3], sample: &AovSample, radiance: DVec3) {
+    for (aov, value) in aovs.iter().zip(sums.iter_mut()) {
+        match aov {
+            Aov::Albedo => *value += sample.albedo,
+            Aov::Normal => *value += sample.normal,
+            Aov::Depth => *value = value.min(DVec3::splat(sample.depth)),
+            Aov::Direct => *value += sample.direct,
+            Aov::Indirect => *value += sample.indirect,
+            Aov::Emission => *value += sample.emission,
+            Aov::Material(kind) => {
+                if sample.material == Some(*kind) {
+                    *value += radiance;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::DVec3;
+    use crate::{camera::CropWindow, tile::{self, TileOrder}};
+    use super::Film;
+
+    #[test]
+    fn tiles_get_their_own_pixels() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            for (width, height) in [(13, 7), (7, 13), (32, 5), (3, 2)] {
+                let mut film = Film::new(CropWindow { x: 0, y: 0, width, height }, (width, height), Vec::new());
+                // Tag every pixel with its position
+                for (index, pixel) in film.pixels.iter_mut().enumerate() {
+                    pixel.sum = DVec3::new((index % width) as f64, (index / width) as f64, 0.0);
+                }
+
+                let size = 5;
+                let tiles = tile::tiles(width, height, size, order);
+                let buckets = film.tiles_mut(&tiles, size);
+                assert_eq!(buckets.len(), tiles.len());
+                for (tile, pixels) in tiles.iter().zip(buckets) {
+                    let positions = pixels.iter().map(|pixel| pixel.sum).collect::<Vec<DVec3>>();
+                    let expected = (tile.y..tile.y + tile.height)
+                        .flat_map(|y| (tile.x..tile.x + tile.width).map(move |x| DVec3::new(x as f64, y as f64, 0.0)))
+                        .collect::<Vec<DVec3>>();
+                    assert_eq!(positions, expected, "{order:?} {width}x{height}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use ::image::{codecs::hdr::HdrEncoder, Rgb};
+use glam::DVec3;
+use crate::{image::Image, tonemap::DisplayTransform};
+
+// Linear, unclamped radiance for every pixel, stored row by row from the top left
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<DVec3>,
+}
+
+impl Framebuffer {
+    // Black framebuffer
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![DVec3::ZERO; width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<DVec3>) -> Framebuffer {
+        assert_eq!(pixels.len(), width * height, "framebuffer needs width * height pixels");
+        Framebuffer { width, height, pixels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[DVec3] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> DVec3 {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: DVec3) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    // Copy another framebuffer in with its top left corner at (x, y), e.g. to stitch crop
+    // windows back into a full frame. Anything falling outside is dropped.
+    pub fn paste(&mut self, x: usize, y: usize, source: &Framebuffer) {
+        for row in 0..source.height.min(self.height.saturating_sub(y)) {
+            for column in 0..source.width.min(self.width.saturating_sub(x)) {
+                self.set(x + column, y + row, source.get(column, row));
+            }
+        }
+    }
+
+    // sRGB encode and quantise for display, clipping anything brighter than white
+    pub fn to_image(&self) -> Image {
+        self.to_image_with(&DisplayTransform::default())
+    }
+
+    // Apply exposure, tone mapping and sRGB encoding, then quantise for display
+    pub fn to_image_with(&self, transform: &DisplayTransform) -> Image {
+        let pixels = self.pixels.iter()
+            .map(|color| transform.to_8bit(*color))
+            .collect::<Vec<[u8; 3]>>();
+
+        Image::from_pixels(self.width, self.height, pixels)
+    }
+
+    // Save to a file chosen by extension. Radiance .hdr and OpenEXR .exr keep the full range,
+    // anything else is converted with to_image first.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let extension = path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("hdr") => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                self.write_hdr(&mut writer)?;
+                writer.flush()
+            },
+            Some("exr") => self.save_exr(path),
+            _ => self.to_image().save(path),
+        }
+    }
+
+    // Radiance RGBE
+    pub fn write_hdr(&self, writer: impl Write) -> io::Result<()> {
+        let pixels = self.pixels.iter()
+            .map(|color| Rgb([color.x as f32, color.y as f32, color.z as f32]))
+            .collect::<Vec<Rgb<f32>>>();
+
+        HdrEncoder::new(writer)
+            .encode(&pixels, self.width, self.height)
+            .map_err(io::Error::other)
+    }
+
+    // OpenEXR with 32-bit float RGB channels
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        exr::prelude::write_rgb_file(path, self.width, self.height, |x, y| {
+            let color = self.get(x, y);
+            (color.x as f32, color.y as f32, color.z as f32)
+        }).map_err(io::Error::other)
+    }
+}
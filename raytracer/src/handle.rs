@@ -0,0 +1,192 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+use crate::render::RenderOutput;
+
+// Shared between a render and whoever is watching it
+pub(crate) struct RenderControl {
+    cancelled: AtomicBool,
+    paused: AtomicBool,
+    clock: Mutex<Clock>,
+    resumed: Condvar,
+    samples_done: AtomicU64,
+    pub(crate) samples_total: u64,
+    rays: AtomicU64,
+}
+
+// Render time, leaving out time spent paused
+struct Clock {
+    started: Instant,
+    paused_at: Option<Instant>,
+    paused_for: Duration,
+}
+
+impl RenderControl {
+    pub(crate) fn new(samples_total: u64) -> Self {
+        RenderControl {
+            cancelled: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            clock: Mutex::new(Clock {
+                started: Instant::now(),
+                paused_at: None,
+                paused_for: Duration::ZERO,
+            }),
+            resumed: Condvar::new(),
+            samples_done: AtomicU64::new(0),
+            samples_total,
+            rays: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    // Block the calling worker while the render is paused
+    pub(crate) fn wait_while_paused(&self) {
+        if !self.paused.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let mut clock = self.clock.lock().unwrap();
+        while clock.paused_at.is_some() && !self.is_cancelled() {
+            clock = self.resumed.wait(clock).unwrap();
+        }
+    }
+
+    pub(crate) fn samples_done(&self) -> u64 {
+        self.samples_done.load(Ordering::Relaxed)
+    }
+
+    // Record finished samples, returning how many are done in total
+    pub(crate) fn add_work(&self, samples: u64, rays: u64) -> u64 {
+        self.rays.fetch_add(rays, Ordering::Relaxed);
+        self.samples_done.fetch_add(samples, Ordering::Relaxed) + samples
+    }
+
+    fn elapsed(&self) -> Duration {
+        let clock = self.clock.lock().unwrap();
+        let paused_now = clock.paused_at.map_or(Duration::ZERO, |paused_at| paused_at.elapsed());
+        clock.started.elapsed().saturating_sub(clock.paused_for + paused_now)
+    }
+}
+
+// A render running on a background thread. Dropping the handle without waiting cancels the render.
+pub struct RenderHandle {
+    control: Arc<RenderControl>,
+    // Taken by wait
+    thread: Option<JoinHandle<RenderOutput>>,
+}
+
+impl RenderHandle {
+    pub(crate) fn spawn(control: Arc<RenderControl>, render: impl FnOnce() -> RenderOutput + Send + 'static) -> Self {
+        RenderHandle {
+            control,
+            thread: Some(std::thread::spawn(render)),
+        }
+    }
+
+    // Fraction of the sample budget done, in [0, 1]. Adaptive sampling can finish
+    // before reaching 1, see is_finished.
+    pub fn progress(&self) -> f64 {
+        if self.is_finished() {
+            return 1.0;
+        }
+
+        let done = self.control.samples_done.load(Ordering::Relaxed);
+        (done as f64 / self.control.samples_total.max(1) as f64).min(1.0)
+    }
+
+    // Time spent rendering so far, not counting pauses
+    pub fn elapsed(&self) -> Duration {
+        self.control.elapsed()
+    }
+
+    // Estimated rendering time left, assuming the rest goes as fast as what is done so far
+    pub fn eta(&self) -> Option<Duration> {
+        let progress = self.progress();
+        if progress >= 1.0 {
+            return Some(Duration::ZERO);
+        }
+        if progress <= 0.0 {
+            return None;
+        }
+
+        Some(self.elapsed().mul_f64((1.0 - progress) / progress))
+    }
+
+    // Rays cast into the scene (camera, bounce and shadow rays) per second of rendering
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.elapsed().as_secs_f64();
+        match seconds > 0.0 {
+            true => self.control.rays.load(Ordering::Relaxed) as f64 / seconds,
+            false => 0.0,
+        }
+    }
+
+    // Workers finish the sample they are on, then block until resume or cancel. They block on the
+    // render's thread pool, which with Threads::Current is the global rayon pool, so anything else
+    // using that pool waits too. Give the render its own pool with Threads::Count to avoid that.
+    pub fn pause(&self) {
+        let mut clock = self.control.clock.lock().unwrap();
+        if clock.paused_at.is_none() {
+            clock.paused_at = Some(Instant::now());
+            self.control.paused.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn resume(&self) {
+        let mut clock = self.control.clock.lock().unwrap();
+        if let Some(paused_at) = clock.paused_at.take() {
+            clock.paused_for += paused_at.elapsed();
+            self.control.paused.store(false, Ordering::Relaxed);
+            self.control.resumed.notify_all();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.control.paused.load(Ordering::Relaxed)
+    }
+
+    // Stop as soon as possible. wait then returns whatever was rendered up to this point.
+    pub fn cancel(&self) {
+        self.control.cancelled.store(true, Ordering::Relaxed);
+        // Paused workers have to wake up to notice
+        let _clock = self.control.clock.lock().unwrap();
+        self.control.resumed.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.control.is_cancelled()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(|thread| thread.is_finished())
+    }
+
+    // Block until the render finishes or, after cancel, stops. A cancelled render gives the partial
+    // image, with pixels that got no samples left black and RenderOutput::sample_counts saying which.
+    // A paused render is resumed first, since nothing could resume it once the handle is gone.
+    pub fn wait(mut self) -> RenderOutput {
+        self.resume();
+        let thread = self.thread.take().expect("render thread is only taken by wait");
+        match thread.join() {
+            Ok(output) => output,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+impl Drop for RenderHandle {
+    // Without a handle nobody could resume or cancel the render, so paused workers would block forever
+    fn drop(&mut self) {
+        if self.thread.is_some() {
+            self.cancel();
+        }
+    }
+}
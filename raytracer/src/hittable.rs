@@ -1,33 +1,101 @@
 use std::ops::Range;
-use glam::DVec3;
-use crate::{material::Material, ray::Ray3};
+use glam::{DVec2, DVec3};
+use crate::{aabb::Aabb, material::Material, ray::Ray3};
 
 pub trait Hittable: Send + Sync {
     #[allow(unused_variables)]
-    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord> { None }
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> { None }
+
+    // Bounds of the object in world space, used to build acceleration structures
+    fn bounding_box(&self) -> Aabb;
+
+    // Solid angle density, seen from `origin`, with which `random` picks `direction`.
+    // Only objects used as lights need to implement this and `random`.
+    #[allow(unused_variables)]
+    fn pdf_value(&self, origin: DVec3, direction: DVec3) -> f64 { 0.0 }
+
+    // Direction from `origin` towards a point on the object, chosen by the uniform sample `u`
+    #[allow(unused_variables)]
+    fn random(&self, origin: DVec3, u: DVec2) -> DVec3 { DVec3::X }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Box<T> {
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
+        (**self).hit(ray, interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn pdf_value(&self, origin: DVec3, direction: DVec3) -> f64 {
+        (**self).pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: DVec3, u: DVec2) -> DVec3 {
+        (**self).random(origin, u)
+    }
 }
 
 #[derive(Copy, Clone)]
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub point: DVec3,
+    // Shading normal, used by materials
     pub normal: DVec3,
+    // True surface normal, always on the same side as the shading normal
+    pub geometric_normal: DVec3,
     pub t: f64,
     pub front_face: bool,
-    pub material: Material,
+    // Weights of the second and third vertex for triangle hits, zero otherwise
+    pub barycentric: DVec2,
+    // Surface texture coordinates
+    pub uv: DVec2,
+    // Index of the hit object in the outermost list or BVH containing it
+    pub object_id: u32,
+    pub material: &'a Material,
 }
 
-impl HitRecord {
-    pub fn with_face_normal(point: DVec3, normal: DVec3, t: f64, material: Material, ray: Ray3, ) -> Self {
+impl<'a> HitRecord<'a> {
+    pub fn with_face_normal(point: DVec3, normal: DVec3, t: f64, material: &'a Material, ray: Ray3, ) -> Self {
         let (front_face, normal) = HitRecord::calculate_face_normal(ray, normal);
         HitRecord {
             point,
             normal,
+            geometric_normal: normal,
             t,
             front_face,
+            barycentric: DVec2::ZERO,
+            uv: DVec2::ZERO,
+            object_id: 0,
             material,
         }
     }
 
+    // Replace the shading normal with an outward facing one (e.g. interpolated from vertex normals),
+    // flipped to match the side the ray hit
+    pub fn with_shading_normal(mut self, outward_normal: DVec3) -> Self {
+        self.normal = match self.front_face {
+            true => outward_normal,
+            false => -outward_normal,
+        };
+        self
+    }
+
+    pub fn with_barycentric(mut self, barycentric: DVec2) -> Self {
+        self.barycentric = barycentric;
+        self
+    }
+
+    pub fn with_uv(mut self, uv: DVec2) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_object_id(mut self, object_id: u32) -> Self {
+        self.object_id = object_id;
+        self
+    }
+
     fn calculate_face_normal(ray: Ray3, outward_normal: DVec3) -> (bool, DVec3) {
         // Dot product is negative if ray comes from outside (points agains normal),
         // and positive if ray comes from inside (points with normal)
@@ -43,6 +111,7 @@ impl HitRecord {
 
 pub struct HittableList {
     objects: Vec<Box<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl Default for HittableList {
@@ -55,36 +124,53 @@ impl HittableList {
     pub fn new() -> Self {
         HittableList {
             objects: Vec::<Box<dyn Hittable>>::new(),
+            bbox: Aabb::EMPTY,
         }
     }
 
     pub fn new_with(object: Box<dyn Hittable>) -> Self {
+        let bbox = object.bounding_box();
         HittableList {
             objects: vec![object],
+            bbox,
         }
     }
 
     pub fn add(&mut self, object: Box<dyn Hittable>) {
+        self.bbox = self.bbox.surrounding(&object.bounding_box());
         self.objects.push(object);
     }
 
     pub fn clear(&mut self) {
         self.objects.clear();
+        self.bbox = Aabb::EMPTY;
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    pub(crate) fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
     }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord> {
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
         let mut closest_record: Option<HitRecord> = None;
         let mut closest_t_so_far = interval.end;
 
         // Loop through all hittable objects in Self::objects
-        for object in self.objects.iter() {
+        for (index, object) in self.objects.iter().enumerate() {
             // Check for a hit between the start and the last closest hit
             match object.hit(ray, interval.start..closest_t_so_far) {
                 Some(record) => {
                     // Save record as the next closest hit
-                    closest_record = Some(record);
+                    closest_record = Some(record.with_object_id(index as u32));
                     closest_t_so_far = record.t;
                 },
                 None => {
@@ -95,4 +181,40 @@ impl Hittable for HittableList {
 
         closest_record
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn pdf_value(&self, origin: DVec3, direction: DVec3) -> f64 {
+        mixture_pdf_value(&self.objects, origin, direction)
+    }
+
+    fn random(&self, origin: DVec3, u: DVec2) -> DVec3 {
+        mixture_random(&self.objects, origin, u)
+    }
+}
+
+// Density of sampling a direction by picking one of `objects` with equal probability
+pub(crate) fn mixture_pdf_value<T: Hittable>(objects: &[T], origin: DVec3, direction: DVec3) -> f64 {
+    if objects.is_empty() {
+        return 0.0;
+    }
+
+    let sum = objects.iter()
+        .map(|object| object.pdf_value(origin, direction))
+        .sum::<f64>();
+    sum / objects.len() as f64
+}
+
+pub(crate) fn mixture_random<T: Hittable>(objects: &[T], origin: DVec3, u: DVec2) -> DVec3 {
+    if objects.is_empty() {
+        return DVec3::X;
+    }
+
+    // Pick an object with the first dimension, then stretch what is left of it back to [0, 1)
+    let scaled = u.x * objects.len() as f64;
+    let index = (scaled as usize).min(objects.len() - 1);
+    let remapped = DVec2::new((scaled - index as f64).min(1.0 - f64::EPSILON), u.y);
+    objects[index].random(origin, remapped)
 }
\ No newline at end of file
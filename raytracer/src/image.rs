@@ -0,0 +1,178 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use ::image::{codecs::png::PngEncoder, ExtendedColorType, ImageEncoder};
+
+// File formats the renderer can write
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    // Binary PPM (P6)
+    Ppm,
+    // Plain-text PPM (P3). Viewers expect .ppm for both, so from_path only picks it for
+    // the made-up .p3 extension, otherwise name it in save_as or write.
+    PpmAscii,
+}
+
+impl ImageFormat {
+    // Guess the format from a file extension: .png, .ppm/.pnm for binary PPM, or .p3 for
+    // plain-text PPM
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" | "pnm" => Some(ImageFormat::Ppm),
+            "p3" => Some(ImageFormat::PpmAscii),
+            _ => None,
+        }
+    }
+}
+
+// 8-bit RGB image, stored row by row from the top left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    // Black image
+    pub fn new(width: usize, height: usize) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![[0, 0, 0]; width * height],
+        }
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<[u8; 3]>) -> Image {
+        assert_eq!(pixels.len(), width * height, "image needs width * height pixels");
+        Image { width, height, pixels }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[[u8; 3]] {
+        &self.pixels
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: [u8; 3]) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    // Save to a file, choosing the format from the extension (see ImageFormat::from_path)
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let format = ImageFormat::from_path(path).ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: unknown image extension", path.display()),
+        ))?;
+        self.save_as(path, format)
+    }
+
+    pub fn save_as(&self, path: impl AsRef<Path>, format: ImageFormat) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write(&mut writer, format)?;
+        writer.flush()
+    }
+
+    pub fn write(&self, writer: impl Write, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Png => self.write_png(writer),
+            ImageFormat::Ppm => self.write_ppm(writer),
+            ImageFormat::PpmAscii => self.write_ppm_ascii(writer),
+        }
+    }
+
+    pub fn write_png(&self, writer: impl Write) -> io::Result<()> {
+        PngEncoder::new(writer)
+            .write_image(self.pixels.as_flattened(), self.width as u32, self.height as u32, ExtendedColorType::Rgb8)
+            .map_err(io::Error::other)
+    }
+
+    pub fn write_ppm(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        writer.write_all(self.pixels.as_flattened())
+    }
+
+    pub fn write_ppm_ascii(&self, mut writer: impl Write) -> io::Result<()> {
+        write!(writer, "P3\n{} {}\n255\n", self.width, self.height)?;
+        for [r, g, b] in &self.pixels {
+            writeln!(writer, "{} {} {}", r, g, b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{Image, ImageFormat};
+
+    fn gradient() -> Image {
+        let (width, height) = (5, 3);
+        let pixels = (0..width * height)
+            .map(|index| [(index * 17) as u8, (255 - index * 9) as u8, (index % width * 60) as u8])
+            .collect::<Vec<[u8; 3]>>();
+        Image::from_pixels(width, height, pixels)
+    }
+
+    fn encode(image: &Image, format: ImageFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image.write(&mut bytes, format).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let image = gradient();
+        let decoded = ::image::load_from_memory(&encode(&image, ImageFormat::Png)).unwrap().into_rgb8();
+
+        assert_eq!((decoded.width() as usize, decoded.height() as usize), (image.width(), image.height()));
+        let pixels = decoded.pixels().map(|pixel| pixel.0).collect::<Vec<[u8; 3]>>();
+        assert_eq!(pixels, image.pixels());
+    }
+
+    #[test]
+    fn ppm_round_trip() {
+        let image = gradient();
+        let bytes = encode(&image, ImageFormat::Ppm);
+
+        let header = b"P6\n5 3\n255\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(&bytes[header.len()..], image.pixels().as_flattened());
+    }
+
+    #[test]
+    fn ppm_ascii_round_trip() {
+        let image = gradient();
+        let text = String::from_utf8(encode(&image, ImageFormat::PpmAscii)).unwrap();
+
+        let mut tokens = text.split_whitespace();
+        assert_eq!(tokens.by_ref().take(4).collect::<Vec<&str>>(), ["P3", "5", "3", "255"]);
+        let values = tokens.map(|token| token.parse::<u8>().unwrap()).collect::<Vec<u8>>();
+        assert_eq!(values, image.pixels().as_flattened());
+    }
+
+    #[test]
+    fn format_from_extension() {
+        assert_eq!(ImageFormat::from_path(Path::new("out.PNG")), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path(Path::new("out.ppm")), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path(Path::new("out.pnm")), Some(ImageFormat::Ppm));
+        assert_eq!(ImageFormat::from_path(Path::new("out.p3")), Some(ImageFormat::PpmAscii));
+        assert_eq!(ImageFormat::from_path(Path::new("out.bmp")), None);
+    }
+}
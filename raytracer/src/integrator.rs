@@ -0,0 +1,319 @@
+use glam::{DVec2, DVec3};
+use crate::{
+    aov::AovSample,
+    color,
+    hittable::{HitRecord, Hittable},
+    ray::Ray3,
+    rng,
+    sampler::Sampler,
+    scene::Scene,
+    stats::{self, PathEnd},
+    vector_utils::{self, Onb},
+};
+
+// Light transport algorithm, run by Camera::render once per camera ray
+pub trait Integrator: Send + Sync {
+    fn radiance(&self, ray: Ray3, scene: &Scene, sampler: &mut dyn Sampler, limits: PathLimits) -> PathSample;
+}
+
+// How far paths may go, set on the camera
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PathLimits {
+    // Most surface hits a path may have
+    pub max_depth: i32,
+    // Surface hits before Russian roulette may end a path
+    pub roulette_depth: i32,
+}
+
+// Result of tracing one camera ray
+#[derive(Debug, Copy, Clone)]
+pub struct PathSample {
+    pub radiance: DVec3,
+    // Number of surfaces the path hit
+    pub length: u32,
+    pub end: PathEnd,
+    pub aov: AovSample,
+}
+
+// Unidirectional path tracer that only follows scattered rays, finding lights by chance
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn radiance(&self, ray: Ray3, scene: &Scene, sampler: &mut dyn Sampler, limits: PathLimits) -> PathSample {
+        trace_path(ray, scene, sampler, limits, false)
+    }
+}
+
+// Path tracer with next-event estimation: every diffuse bounce also aims a shadow ray at the scene lights,
+// combined with the scattered ray by multiple importance sampling
+#[derive(Debug, Default, Copy, Clone)]
+pub struct NeePathTracer;
+
+impl Integrator for NeePathTracer {
+    fn radiance(&self, ray: Ray3, scene: &Scene, sampler: &mut dyn Sampler, limits: PathLimits) -> PathSample {
+        trace_path(ray, scene, sampler, limits, true)
+    }
+}
+
+// Fraction of the hemisphere above the first hit that is open within `distance`, white where nothing is hit
+#[derive(Debug, Copy, Clone)]
+pub struct AmbientOcclusion {
+    pub distance: f64,
+}
+
+impl Integrator for AmbientOcclusion {
+    fn radiance(&self, ray: Ray3, scene: &Scene, sampler: &mut dyn Sampler, _limits: PathLimits) -> PathSample {
+        let Some(record) = scene.hit(ray, 0.001..f64::INFINITY) else {
+            return PathSample { radiance: DVec3::ONE, length: 0, end: PathEnd::Escaped, aov: AovSample::default() };
+        };
+
+        // Cosine-weighted directions, so the unoccluded fraction is the cosine-weighted visibility
+        let direction = Onb::new(record.normal).local(vector_utils::sample_cosine_hemisphere(sampler.get_2d()));
+        let occluded = scene.hit(Ray3::new(record.point, direction), 0.001..self.distance).is_some();
+        let radiance = match occluded {
+            true => DVec3::ZERO,
+            false => DVec3::ONE,
+        };
+
+        let aov = AovSample::at_first_hit(ray, &record);
+        PathSample { radiance, length: 1, end: PathEnd::Absorbed, aov }
+    }
+}
+
+// Whitted-style ray tracing: mirrors and glass are followed, diffuse surfaces only see the scene lights
+// directly, with no indirect bounce
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Whitted;
+
+impl Integrator for Whitted {
+    fn radiance(&self, ray: Ray3, scene: &Scene, sampler: &mut dyn Sampler, limits: PathLimits) -> PathSample {
+        let mut aov = AovSample::default();
+        let mut throughput = DVec3::ONE;
+        let mut ray = ray;
+        let mut length = 0;
+
+        let end = loop {
+            let Some(record) = scene.hit(ray, 0.001..f64::INFINITY) else {
+                aov.add_light(length, throughput * scene.environment.color(ray.direction));
+                break PathEnd::Escaped;
+            };
+            if length == 0 {
+                aov = AovSample::at_first_hit(ray, &record);
+            }
+            aov.add_light(length, throughput * record.material.emitted(&record));
+            length += 1;
+
+            let Some(scattered) = record.material.scatter(ray, &record, sampler) else {
+                break PathEnd::Absorbed;
+            };
+            let u_light = sampler.get_2d();
+
+            if scattered.pdf.is_some() {
+                if let Some(light) = sample_light(scene, &record, u_light) {
+                    aov.add_light(length, throughput * light.radiance);
+                }
+                break PathEnd::Absorbed;
+            }
+
+            if length >= limits.max_depth as u32 {
+                break PathEnd::MaxDepth;
+            }
+
+            throughput *= scattered.attenuation;
+            ray = scattered.scattered;
+        };
+
+        PathSample { radiance: aov.radiance(), length, end, aov }
+    }
+}
+
+// False-colour views of the first surface hit, for checking geometry
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DebugView {
+    // Shading normal mapped from [-1, 1] to [0, 1]
+    Normal,
+    // Planar depth, the distance along `view_direction` (Camera::view_direction) from the camera,
+    // going from black at the camera to white at `far`
+    Depth { far: f64, view_direction: DVec3 },
+    // Green where the outside of a surface was hit, red for the inside
+    FrontFace,
+    // Surface color of the material, without lighting
+    Albedo,
+    // A different color for each object in the world
+    ObjectId,
+    // Texture coordinates in red and green
+    Uv,
+    // Heatmap of ray/primitive tests for the camera ray, saturating at `max`.
+    // Needs the intersection-stats feature, without which it stays blue.
+    PrimitiveTests { max: u32 },
+    // Heatmap of ray/box tests against BVH nodes for the camera ray, saturating at `max`.
+    // Needs the intersection-stats feature, without which it stays blue.
+    BoxTests { max: u32 },
+}
+
+impl Integrator for DebugView {
+    fn radiance(&self, ray: Ray3, scene: &Scene, _sampler: &mut dyn Sampler, _limits: PathLimits) -> PathSample {
+        let before = stats::intersection_counts();
+        let hit = scene.hit(ray, 0.001..f64::INFINITY);
+        let after = stats::intersection_counts();
+
+        let surface_hit = hit.is_some();
+        let aov = hit.as_ref()
+            .map(|record| AovSample::at_first_hit(ray, record))
+            .unwrap_or_default();
+
+        // Heatmaps also cover rays that missed everything
+        let color = match (*self, hit) {
+            (DebugView::PrimitiveTests { max }, _) => {
+                color::heatmap((after.primitives - before.primitives) as f64 / max.max(1) as f64)
+            },
+            (DebugView::BoxTests { max }, _) => {
+                color::heatmap((after.boxes - before.boxes) as f64 / max.max(1) as f64)
+            },
+            (_, None) => DVec3::ZERO,
+            (DebugView::Normal, Some(record)) => 0.5 * (record.normal + DVec3::ONE),
+            (DebugView::Depth { far, view_direction }, Some(record)) => {
+                // Camera rays start on the lens plane, so this is the distance from that plane
+                let depth = record.t * ray.direction.dot(view_direction.normalize());
+                DVec3::splat(depth / far)
+            },
+            (DebugView::FrontFace, Some(record)) => match record.front_face {
+                true => DVec3::new(0.0, 1.0, 0.0),
+                false => DVec3::new(1.0, 0.0, 0.0),
+            },
+            (DebugView::Albedo, Some(record)) => record.material.albedo(&record),
+            (DebugView::ObjectId, Some(record)) => id_color(record.object_id),
+            (DebugView::Uv, Some(record)) => DVec3::new(record.uv.x, record.uv.y, 0.0),
+        };
+
+        match surface_hit {
+            true => PathSample { radiance: color, length: 1, end: PathEnd::Absorbed, aov },
+            false => PathSample { radiance: color, length: 0, end: PathEnd::Escaped, aov },
+        }
+    }
+}
+
+// Stable, well separated color for an object ID
+fn id_color(id: u32) -> DVec3 {
+    let hash = rng::splitmix64(id as u64);
+    let channel = |shift: u32| 0.15 + 0.85 * ((hash >> shift) & 0xff) as f64 / 255.0;
+    DVec3::new(channel(0), channel(8), channel(16))
+}
+
+// Follow a path of at most `max_depth` surface hits. Paths longer than `roulette_depth` are
+// randomly terminated once their throughput gets small, with survivors weighted up to compensate.
+fn trace_path(ray: Ray3, scene: &Scene, sampler: &mut dyn Sampler, limits: PathLimits, light_sampling: bool) -> PathSample {
+    let mut aov = AovSample::default();
+    let mut throughput = DVec3::ONE;
+    let mut ray = ray;
+    // Density with which the previous bounce picked `ray`, or None for camera rays
+    // and specular bounces, whose paths light sampling can't find
+    let mut bsdf_pdf: Option<f64> = None;
+    let mut length = 0;
+
+    let end = loop {
+        let Some(record) = scene.hit(ray, 0.001..f64::INFINITY) else {
+            aov.add_light(length, throughput * scene.environment.color(ray.direction));
+            break PathEnd::Escaped;
+        };
+        if length == 0 {
+            aov = AovSample::at_first_hit(ray, &record);
+        }
+
+        // Lights found by chance are weighted against the chance light sampling found them first
+        let mut emitted = record.material.emitted(&record);
+        if let (true, Some(bsdf_pdf)) = (light_sampling, bsdf_pdf) {
+            if emitted != DVec3::ZERO {
+                let light_pdf = scene.lights.pdf_value(ray.origin, ray.direction);
+                emitted *= power_heuristic(bsdf_pdf, light_pdf);
+            }
+        }
+        aov.add_light(length, throughput * emitted);
+        length += 1;
+
+        if length >= limits.max_depth as u32 {
+            break PathEnd::MaxDepth;
+        }
+
+        let Some(scattered) = record.material.scatter(ray, &record, sampler) else {
+            break PathEnd::Absorbed;
+        };
+
+        // Always taken, so later bounces use the same sample dimensions
+        let u_light = sampler.get_2d();
+        let u_roulette = sampler.get_1d();
+
+        if light_sampling && scattered.pdf.is_some() {
+            if let Some(light) = sample_light(scene, &record, u_light) {
+                let weight = power_heuristic(light.light_pdf, light.bsdf_pdf);
+                aov.add_light(length, throughput * light.radiance * weight);
+            }
+        }
+
+        throughput *= scattered.attenuation;
+        if throughput == DVec3::ZERO {
+            break PathEnd::Absorbed;
+        }
+
+        if length >= limits.roulette_depth as u32 {
+            let survival = throughput.max_element().min(1.0);
+            if u_roulette >= survival {
+                break PathEnd::Roulette;
+            }
+            throughput /= survival;
+        }
+
+        ray = scattered.scattered;
+        bsdf_pdf = scattered.pdf;
+    };
+
+    PathSample { radiance: aov.radiance(), length, end, aov }
+}
+
+struct LightSample {
+    // Reflected light divided by the light pdf, before any MIS weight
+    radiance: DVec3,
+    light_pdf: f64,
+    // Density with which the material would have scattered towards the same point
+    bsdf_pdf: f64,
+}
+
+// Light reaching a surface directly from a point picked on one of the scene lights
+fn sample_light(scene: &Scene, record: &HitRecord, u: DVec2) -> Option<LightSample> {
+    if scene.lights.is_empty() {
+        return None;
+    }
+
+    let direction = scene.lights.random(record.point, u).normalize_or_zero();
+    let light_pdf = scene.lights.pdf_value(record.point, direction);
+    if light_pdf <= 0.0 {
+        return None;
+    }
+
+    let reflected = record.material.evaluate(record, direction);
+    if reflected == DVec3::ZERO {
+        return None;
+    }
+
+    // The shadow ray carries whatever it hits first, so an occluded light contributes nothing
+    let light_record = scene.hit(Ray3::new(record.point, direction), 0.001..f64::INFINITY)?;
+    let emitted = light_record.material.emitted(&light_record);
+
+    Some(LightSample {
+        radiance: reflected * emitted / light_pdf,
+        light_pdf,
+        bsdf_pdf: record.material.pdf(record, direction),
+    })
+}
+
+// Veach's power heuristic (beta = 2) for the weight of a sample drawn from the first of two strategies
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    let sum = pdf_squared + other_pdf * other_pdf;
+    if sum <= 0.0 {
+        return 0.0;
+    }
+
+    pdf_squared / sum
+}
@@ -1,6 +1,30 @@
 pub mod vector_utils;
 pub mod ray;
+pub mod environment;
+pub mod aabb;
 pub mod hittable;
+pub mod bvh;
 pub mod sphere;
+pub mod triangle;
+pub mod obj;
+pub mod scene;
 pub mod camera;
-pub mod material;
\ No newline at end of file
+pub mod integrator;
+pub mod aov;
+pub mod render;
+pub mod handle;
+pub mod progress;
+pub mod tile;
+pub mod rng;
+pub mod sampler;
+pub mod stats;
+pub mod material;
+pub mod image;
+pub mod framebuffer;
+pub(crate) mod film;
+pub(crate) mod checkpoint;
+pub mod color;
+pub mod denoise;
+pub mod tonemap;
+pub mod texture;
+pub mod perlin;
\ No newline at end of file
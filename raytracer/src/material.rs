@@ -1,12 +1,12 @@
+use std::f64::consts::PI;
 use glam::DVec3;
-use rand::Rng;
 
-use crate::{hittable::HitRecord, ray::Ray3, vector_utils};
+use crate::{hittable::HitRecord, ray::Ray3, sampler::Sampler, texture::Texture, vector_utils::{self, Onb}};
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub enum Material {
     Lambertian {
-        albedo: DVec3,
+        albedo: Texture,
     },
     Metal {
         albedo: DVec3,
@@ -14,30 +14,50 @@ pub enum Material {
     },
     Dielectric  {
         refraction_index: f64,
-    }
+    },
+    // Emits light and does not scatter
+    DiffuseLight {
+        emit: Texture,
+    },
+}
+
+// Material variants without their parameters
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MaterialKind {
+    Lambertian,
+    Metal,
+    Dielectric,
+    DiffuseLight,
 }
 
 pub struct Scattered {
     pub scattered: Ray3,
+    // BSDF times cosine divided by the pdf, i.e. the path throughput weight of this bounce
     pub attenuation: DVec3,
+    // Solid angle density of the scattered direction, None for specular bounces
+    // which light sampling can never reproduce
+    pub pdf: Option<f64>,
 }
 
 impl Material {
-    pub fn scatter(self, incident_ray: Ray3, record: HitRecord) -> Option<Scattered> {
+    pub fn scatter(&self, incident_ray: Ray3, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scattered> {
+        // Every bounce takes the same sample dimensions, whichever material was hit
+        let u = sampler.get_2d();
+        let u_choice = sampler.get_1d();
+
         match self {
             Material::Lambertian { albedo } => {
-                let mut scattered_direction = record.normal + vector_utils::random_unit_vector();
-
-                if vector_utils::near_zero(scattered_direction) {
-                    scattered_direction = record.normal;
-                }
+                // Cosine-weighted, so the cosine and 1 / pi cancel against the pdf
+                let local = vector_utils::sample_cosine_hemisphere(u);
+                let scattered_direction = Onb::new(record.normal).local(local);
 
                 Some(Scattered {
                     scattered: Ray3 {
                         origin: record.point,
                         direction: scattered_direction,
                     },
-                    attenuation: albedo,
+                    attenuation: albedo.value(record.uv, record.point),
+                    pdf: Some(local.z / PI),
                 })
             },
 
@@ -45,14 +65,15 @@ impl Material {
                 let reflected_direction = 
                     reflect(incident_ray.direction, record.normal)
                     .normalize()
-                    + (fuzz * vector_utils::random_unit_vector());
+                    + (fuzz * vector_utils::sample_unit_vector(u));
                 
                 let scattered = Ray3::new(record.point, reflected_direction);
 
                 if scattered.direction.dot(record.normal) > 0.0 {
                     Some(Scattered {
                         scattered,
-                        attenuation: albedo,
+                        attenuation: *albedo,
+                        pdf: None,
                     })
                 } else {
                     None
@@ -61,12 +82,10 @@ impl Material {
             },
 
             Material::Dielectric { refraction_index } => {
-                let mut rng = rand::rng();
-
                 let attenuation = DVec3::new(1.0, 1.0, 1.0);
                 let refraction_index_corrected = match record.front_face {
                     true => refraction_index.recip(),
-                    false => refraction_index,
+                    false => *refraction_index,
                 };
                 let unit_direction = incident_ray.direction.normalize();
 
@@ -76,7 +95,7 @@ impl Material {
 
                 let cannot_refract = refraction_index_corrected * sin_theta > 1.0;
 
-                let direction = if cannot_refract || (reflectance(cos_theta, refraction_index_corrected) > rng.random_range(0.0..1.0)) {
+                let direction = if cannot_refract || (reflectance(cos_theta, refraction_index_corrected) > u_choice) {
                     // No refraction solution, so reflect
                     reflect(unit_direction, record.normal)
                 } else {
@@ -90,8 +109,55 @@ impl Material {
                         direction,
                     },
                     attenuation,
+                    pdf: None,
                 })
             },
+
+            Material::DiffuseLight { .. } => None,
+        }
+    }
+
+    // BSDF times cosine for light arriving from `direction`, zero for specular materials.
+    // Used to weight light samples, `direction` must be normalised.
+    pub fn evaluate(&self, record: &HitRecord, direction: DVec3) -> DVec3 {
+        match self {
+            Material::Lambertian { albedo } => albedo.value(record.uv, record.point) * self.pdf(record, direction),
+            _ => DVec3::ZERO,
+        }
+    }
+
+    // Density with which scatter would choose `direction`, zero for specular materials
+    pub fn pdf(&self, record: &HitRecord, direction: DVec3) -> f64 {
+        match self {
+            Material::Lambertian { .. } => direction.dot(record.normal).max(0.0) / PI,
+            _ => 0.0,
+        }
+    }
+
+    pub fn kind(&self) -> MaterialKind {
+        match self {
+            Material::Lambertian { .. } => MaterialKind::Lambertian,
+            Material::Metal { .. } => MaterialKind::Metal,
+            Material::Dielectric { .. } => MaterialKind::Dielectric,
+            Material::DiffuseLight { .. } => MaterialKind::DiffuseLight,
+        }
+    }
+
+    // Overall surface color, ignoring lighting. Glass counts as white.
+    pub fn albedo(&self, record: &HitRecord) -> DVec3 {
+        match self {
+            Material::Lambertian { albedo } => albedo.value(record.uv, record.point),
+            Material::Metal { albedo, .. } => *albedo,
+            Material::Dielectric { .. } => DVec3::ONE,
+            Material::DiffuseLight { emit } => emit.value(record.uv, record.point).clamp(DVec3::ZERO, DVec3::ONE),
+        }
+    }
+
+    // Light given off by the surface, zero for everything but lights
+    pub fn emitted(&self, record: &HitRecord) -> DVec3 {
+        match self {
+            Material::DiffuseLight { emit } => emit.value(record.uv, record.point),
+            _ => DVec3::ZERO,
         }
     }
 }
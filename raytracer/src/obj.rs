@@ -0,0 +1,559 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+use glam::{DVec2, DVec3};
+use crate::{
+    material::Material,
+    texture::Texture,
+    triangle::{MeshData, TriangleMesh},
+};
+
+// Material parameters as read from a .mtl file
+#[derive(Debug, Clone)]
+pub struct MtlMaterial {
+    pub name: String,
+    // Kd
+    pub diffuse: DVec3,
+    // Ks
+    pub specular: DVec3,
+    // Ns
+    pub shininess: f64,
+    // Ni
+    pub refraction_index: f64,
+    // d, or 1 - Tr
+    pub dissolve: f64,
+    // map_Kd, resolved relative to the .mtl file
+    pub diffuse_map: Option<PathBuf>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            name: String::new(),
+            diffuse: DVec3::splat(0.8),
+            specular: DVec3::ZERO,
+            shininess: 0.0,
+            refraction_index: 1.0,
+            dissolve: 1.0,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Pick the closest of the renderer's materials, loading the diffuse map if there is one
+    pub fn to_material(&self) -> io::Result<Material> {
+        let material = if self.dissolve < 1.0 {
+            // Transparent surfaces become glass, defaulting to a typical index if Ni was left at 1
+            let refraction_index = match self.refraction_index > 1.0 {
+                true => self.refraction_index,
+                false => 1.5,
+            };
+            Material::Dielectric { refraction_index }
+        } else if self.specular.max_element() > self.diffuse.max_element() {
+            // Mostly specular, so treat as metal with roughness derived from the Phong exponent
+            let fuzz = (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt();
+            Material::Metal {
+                albedo: self.specular.clamp(DVec3::ZERO, DVec3::ONE),
+                fuzz,
+            }
+        } else {
+            let albedo = match &self.diffuse_map {
+                Some(path) => Texture::image(path)?,
+                None => Texture::Solid(self.diffuse.clamp(DVec3::ZERO, DVec3::ONE)),
+            };
+            Material::Lambertian { albedo }
+        };
+
+        Ok(material)
+    }
+}
+
+// One group/material combination from an .obj file
+pub struct ObjObject {
+    pub name: String,
+    // None when no material was set, or the one named isn't in the MTL libraries,
+    // in which case the mesh uses the default material
+    pub material: Option<MtlMaterial>,
+    pub mesh: TriangleMesh,
+}
+
+// Load an .obj file and any .mtl libraries it references.
+// Faces are split into one mesh per group and material, so the results can be added to a HittableList.
+pub fn load_obj(path: impl AsRef<Path>) -> io::Result<Vec<ObjObject>> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    File::open(path)
+        .and_then(|file| parse_obj(BufReader::new(file), base_dir))
+        .map_err(|e| with_path(e, path))
+}
+
+// Load every material from a .mtl file
+pub fn load_mtl(path: impl AsRef<Path>) -> io::Result<Vec<MtlMaterial>> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    File::open(path)
+        .and_then(|file| parse_mtl(BufReader::new(file), base_dir))
+        .map_err(|e| with_path(e, path))
+}
+
+// Indices into the file-wide position, uv and normal pools
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+#[derive(Default)]
+struct FaceGroup {
+    name: String,
+    material: Option<String>,
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+pub fn parse_obj(reader: impl BufRead, base_dir: &Path) -> io::Result<Vec<ObjObject>> {
+    let mut positions = Vec::<DVec3>::new();
+    let mut uvs = Vec::<DVec2>::new();
+    let mut normals = Vec::<DVec3>::new();
+    let mut materials = HashMap::<String, MtlMaterial>::new();
+
+    let mut groups = Vec::<FaceGroup>::new();
+    let mut group_name = String::from("default");
+    let mut material_name: Option<String> = None;
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let error = |message: &str| invalid_data(format!("line {}: {}", line_number + 1, message));
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let arguments = tokens.collect::<Vec<&str>>();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&arguments).ok_or_else(|| error("bad vertex position"))?),
+            "vn" => normals.push(parse_vec3(&arguments).ok_or_else(|| error("bad vertex normal"))?),
+            "vt" => {
+                let u = parse_float(arguments.first()).ok_or_else(|| error("bad texture coordinate"))?;
+                // v is optional for 1D textures
+                let v = parse_float(arguments.get(1)).unwrap_or(0.0);
+                uvs.push(DVec2::new(u, v));
+            },
+            "g" | "o" => {
+                group_name = match arguments.is_empty() {
+                    true => String::from("default"),
+                    false => arguments.join(" "),
+                };
+            },
+            "usemtl" => material_name = arguments.first().map(|name| name.to_string()),
+            "mtllib" => {
+                // A missing or broken library only loses its materials, faces fall back to the default
+                for library in arguments {
+                    for material in load_mtl(base_dir.join(library)).unwrap_or_default() {
+                        materials.insert(material.name.clone(), material);
+                    }
+                }
+            },
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(error("face with fewer than three vertices"));
+                }
+                let face = arguments.iter()
+                    .map(|vertex| parse_face_vertex(vertex, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Option<Vec<FaceVertex>>>()
+                    .ok_or_else(|| error("bad face index"))?;
+
+                let group = match groups.last_mut() {
+                    Some(group) if group.name == group_name && group.material == material_name => group,
+                    _ => {
+                        groups.push(FaceGroup {
+                            name: group_name.clone(),
+                            material: material_name.clone(),
+                            faces: Vec::new(),
+                        });
+                        groups.last_mut().unwrap()
+                    }
+                };
+
+                let points = face.iter().map(|vertex| positions[vertex.0]).collect::<Vec<DVec3>>();
+                for [a, b, c] in triangulate(&points) {
+                    group.faces.push([face[a], face[b], face[c]]);
+                }
+            },
+            // Smoothing groups, lines, points and the rest are not needed for rendering
+            _ => {},
+        }
+    }
+
+    // Merge groups that were split up by interleaved statements
+    let mut merged = Vec::<FaceGroup>::new();
+    for group in groups {
+        match merged.iter_mut().find(|g| g.name == group.name && g.material == group.material) {
+            Some(existing) => existing.faces.extend(group.faces),
+            None => merged.push(group),
+        }
+    }
+
+    // Converted materials are cached so each texture is only loaded once
+    let mut render_materials = HashMap::<Option<String>, Material>::new();
+
+    let objects = merged.into_iter()
+        .filter(|group| !group.faces.is_empty())
+        .map(|group| {
+            // Assets often name materials their MTL files don't define, which get the default
+            let material = group.material.as_ref().and_then(|name| materials.get(name).cloned());
+            let render_material = match render_materials.get(&group.material) {
+                Some(render_material) => render_material.clone(),
+                None => {
+                    let render_material = material.clone().unwrap_or_default().to_material()?;
+                    render_materials.insert(group.material.clone(), render_material.clone());
+                    render_material
+                },
+            };
+            let data = build_mesh_data(&group.faces, &positions, &uvs, &normals);
+
+            Ok(ObjObject {
+                name: group.name,
+                material,
+                mesh: TriangleMesh::new(data, render_material),
+            })
+        })
+        .collect::<io::Result<Vec<ObjObject>>>()?;
+
+    Ok(objects)
+}
+
+pub fn parse_mtl(reader: impl BufRead, base_dir: &Path) -> io::Result<Vec<MtlMaterial>> {
+    let mut materials = Vec::<MtlMaterial>::new();
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let error = |message: &str| invalid_data(format!("line {}: {}", line_number + 1, message));
+
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+        let arguments = tokens.collect::<Vec<&str>>();
+
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial {
+                name: arguments.join(" "),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(error("material statement before newmtl")),
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = parse_color(&arguments).ok_or_else(|| error("bad Kd"))?,
+            "Ks" => material.specular = parse_color(&arguments).ok_or_else(|| error("bad Ks"))?,
+            "Ns" => material.shininess = parse_float(arguments.first()).ok_or_else(|| error("bad Ns"))?,
+            "Ni" => material.refraction_index = parse_float(arguments.first()).ok_or_else(|| error("bad Ni"))?,
+            "d" => material.dissolve = parse_float(arguments.last()).ok_or_else(|| error("bad d"))?,
+            "Tr" => material.dissolve = 1.0 - parse_float(arguments.last()).ok_or_else(|| error("bad Tr"))?,
+            // Options such as -bm come first, the file name is always last
+            "map_Kd" => {
+                let file = arguments.last().ok_or_else(|| error("map_Kd without a file"))?;
+                material.diffuse_map = Some(base_dir.join(file));
+            },
+            _ => {},
+        }
+    }
+
+    Ok(materials)
+}
+
+// Give each group its own compact vertex buffers, sharing vertices used by several faces
+fn build_mesh_data(faces: &[[FaceVertex; 3]], positions: &[DVec3], uvs: &[DVec2], normals: &[DVec3]) -> MeshData {
+    // Per-vertex attributes are only kept if every vertex of the group has them
+    let has_uvs = faces.iter().flatten().all(|vertex| vertex.1.is_some());
+    let has_normals = faces.iter().flatten().all(|vertex| vertex.2.is_some());
+
+    let mut data = MeshData::default();
+    let mut vertex_lookup = HashMap::<FaceVertex, u32>::new();
+
+    for face in faces {
+        let indices = face.map(|vertex| {
+            *vertex_lookup.entry(vertex).or_insert_with(|| {
+                data.positions.push(positions[vertex.0]);
+                if has_uvs {
+                    data.uvs.push(uvs[vertex.1.unwrap()]);
+                }
+                if has_normals {
+                    data.normals.push(normals[vertex.2.unwrap()]);
+                }
+                (data.positions.len() - 1) as u32
+            })
+        });
+        data.indices.push(indices);
+    }
+
+    data
+}
+
+// Parse "v", "v/vt", "v//vn" or "v/vt/vn", resolving 1-based and negative (relative) indices
+fn parse_face_vertex(vertex: &str, position_count: usize, uv_count: usize, normal_count: usize) -> Option<FaceVertex> {
+    let mut parts = vertex.split('/');
+    let position = resolve_index(parts.next()?, position_count)?;
+    let uv = match parts.next() {
+        Some(index) if !index.is_empty() => Some(resolve_index(index, uv_count)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(index) if !index.is_empty() => Some(resolve_index(index, normal_count)?),
+        _ => None,
+    };
+    Some((position, uv, normal))
+}
+
+fn resolve_index(index: &str, count: usize) -> Option<usize> {
+    let index = index.parse::<i64>().ok()?;
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => count as i64 + i,
+        _ => return None,
+    };
+    (0..count as i64).contains(&resolved).then_some(resolved as usize)
+}
+
+// Split a planar polygon into triangles by ear clipping, which also handles concave polygons.
+// Returns indices into the polygon's vertex list.
+fn triangulate(points: &[DVec3]) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method gives a robust normal for non-planar polygons
+    let normal = (0..points.len()).fold(DVec3::ZERO, |acc, i| {
+        let current = points[i];
+        let next = points[(i + 1) % points.len()];
+        acc + DVec3::new(
+            (current.y - next.y) * (current.z + next.z),
+            (current.z - next.z) * (current.x + next.x),
+            (current.x - next.x) * (current.y + next.y),
+        )
+    });
+
+    // Project onto the plane most aligned with the polygon, keeping counter-clockwise winding
+    let abs_normal = normal.abs();
+    let (x_axis, y_axis, sign) = if abs_normal.x >= abs_normal.y && abs_normal.x >= abs_normal.z {
+        (1, 2, normal.x.signum())
+    } else if abs_normal.y >= abs_normal.z {
+        (2, 0, normal.y.signum())
+    } else {
+        (0, 1, normal.z.signum())
+    };
+    let projected = points.iter()
+        .map(|p| DVec2::new(p[x_axis], sign * p[y_axis]))
+        .collect::<Vec<DVec2>>();
+
+    let cross = |a: DVec2, b: DVec2, c: DVec2| (b - a).perp_dot(c - a);
+
+    let mut remaining = (0..points.len()).collect::<Vec<usize>>();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let prev = remaining[(i + count - 1) % count];
+            let current = remaining[i];
+            let next = remaining[(i + 1) % count];
+            let (a, b, c) = (projected[prev], projected[current], projected[next]);
+
+            // Must be a convex corner with no other vertex inside it
+            cross(a, b, c) > 0.0 && remaining.iter()
+                .filter(|&&other| other != prev && other != current && other != next)
+                .all(|&other| {
+                    let p = projected[other];
+                    cross(a, b, p) < 0.0 || cross(b, c, p) < 0.0 || cross(c, a, p) < 0.0
+                })
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]]);
+                remaining.remove(i);
+            },
+            // Degenerate or self-intersecting polygon, so fall back to a fan
+            None => break,
+        }
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+
+    triangles
+}
+
+fn parse_float(token: Option<&&str>) -> Option<f64> {
+    token?.parse::<f64>().ok()
+}
+
+fn parse_vec3(arguments: &[&str]) -> Option<DVec3> {
+    Some(DVec3::new(
+        parse_float(arguments.first())?,
+        parse_float(arguments.get(1))?,
+        parse_float(arguments.get(2))?,
+    ))
+}
+
+// A single value is used for all three channels
+fn parse_color(arguments: &[&str]) -> Option<DVec3> {
+    match arguments.len() {
+        1 => parse_float(arguments.first()).map(DVec3::splat),
+        _ => parse_vec3(arguments),
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn with_path(error: io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use glam::{DVec2, DVec3};
+    use crate::material::MaterialKind;
+    use super::{parse_mtl, parse_obj, ObjObject};
+
+    fn parse(source: &str) -> Vec<ObjObject> {
+        parse_obj(source.as_bytes(), Path::new("missing")).unwrap()
+    }
+
+    #[test]
+    fn polygons_are_triangulated() {
+        let objects = parse("
+            v 0 0 0
+            v 2 0 0
+            v 2 1 0
+            v 0 1 0
+            f 1 2 3 4
+            g lshape
+            v 0 0 1
+            v 2 0 1
+            v 2 1 1
+            v 1 1 1
+            v 1 2 1
+            v 0 2 1
+            f 5 6 7 8 9 10
+        ");
+
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].mesh.triangle_count(), 2);
+        assert!((objects[0].mesh.surface_area() - 2.0).abs() < 1e-12);
+        // The concave corner has to be clipped correctly for the area to come out right
+        assert_eq!(objects[1].name, "lshape");
+        assert_eq!(objects[1].mesh.triangle_count(), 4);
+        assert!((objects[1].mesh.surface_area() - 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_latest_vertex() {
+        let objects = parse("
+            v 9 9 9
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f -3 -2 -1
+        ");
+
+        let data = objects[0].mesh.data();
+        assert_eq!(data.positions, vec![DVec3::ZERO, DVec3::X, DVec3::Y]);
+        assert_eq!(data.indices, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn face_vertex_variants() {
+        let source = |face: &str| format!("
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 0 1
+            vn 0 0 1
+            vn 0 0 1
+            vn 0 0 1
+            f {face}
+        ");
+
+        let positions_only = parse(&source("1 2 3"));
+        assert!(positions_only[0].mesh.data().uvs.is_empty());
+        assert!(positions_only[0].mesh.data().normals.is_empty());
+
+        let with_uvs = parse(&source("1/1 2/2 3/3"));
+        assert_eq!(with_uvs[0].mesh.data().uvs, vec![DVec2::ZERO, DVec2::X, DVec2::Y]);
+        assert!(with_uvs[0].mesh.data().normals.is_empty());
+
+        let with_normals = parse(&source("1//1 2//2 3//3"));
+        assert!(with_normals[0].mesh.data().uvs.is_empty());
+        assert_eq!(with_normals[0].mesh.data().normals, vec![DVec3::Z; 3]);
+
+        let with_both = parse(&source("1/1/1 2/2/2 3/3/3"));
+        assert_eq!(with_both[0].mesh.data().uvs.len(), 3);
+        assert_eq!(with_both[0].mesh.data().normals.len(), 3);
+
+        assert!(parse_obj(source("1 2 4").as_bytes(), Path::new("missing")).is_err());
+    }
+
+    #[test]
+    fn mtl_statements_map_onto_materials() {
+        let materials = parse_mtl("
+            newmtl plastic
+            Kd 0.5 0.25 0.125
+            newmtl chrome
+            Kd 0.1
+            Ks 0.9 0.9 0.9
+            Ns 100
+            newmtl glass
+            Ni 1.33
+            d 0.2
+            newmtl tinted
+            Tr 0.75
+        ".as_bytes(), Path::new("assets")).unwrap();
+
+        let names = materials.iter().map(|material| material.name.as_str()).collect::<Vec<&str>>();
+        assert_eq!(names, ["plastic", "chrome", "glass", "tinted"]);
+
+        assert_eq!(materials[0].diffuse, DVec3::new(0.5, 0.25, 0.125));
+        assert_eq!(materials[1].diffuse, DVec3::splat(0.1));
+        assert_eq!(materials[1].specular, DVec3::splat(0.9));
+        assert_eq!(materials[1].shininess, 100.0);
+        assert_eq!(materials[2].refraction_index, 1.33);
+        assert_eq!(materials[2].dissolve, 0.2);
+        assert_eq!(materials[3].dissolve, 0.25);
+
+        let kinds = materials.iter()
+            .map(|material| material.to_material().unwrap().kind())
+            .collect::<Vec<MaterialKind>>();
+        assert_eq!(kinds, [MaterialKind::Lambertian, MaterialKind::Metal, MaterialKind::Dielectric, MaterialKind::Dielectric]);
+    }
+
+    #[test]
+    fn unknown_materials_and_libraries_fall_back_to_the_default() {
+        let objects = parse("
+            mtllib nowhere.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            usemtl undefined
+            f 1 2 3
+        ");
+
+        assert_eq!(objects.len(), 1);
+        assert!(objects[0].material.is_none());
+        assert_eq!(objects[0].mesh.triangle_count(), 1);
+    }
+}
@@ -0,0 +1,98 @@
+use glam::DVec3;
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use crate::{rng::RenderRng, vector_utils};
+
+const POINT_COUNT: usize = 256;
+
+// Gradient (Perlin) noise over 3D space
+pub struct Perlin {
+    gradients: Vec<DVec3>,
+    permute_x: Vec<usize>,
+    permute_y: Vec<usize>,
+    permute_z: Vec<usize>,
+}
+
+// Fixed seed, so noise textures look the same in every render
+impl Default for Perlin {
+    fn default() -> Self {
+        Self::new(&mut RenderRng::seed_from_u64(0))
+    }
+}
+
+impl Perlin {
+    pub fn new(rng: &mut impl Rng) -> Self {
+        let gradients = (0..POINT_COUNT)
+            .map(|_| vector_utils::random_unit_vector(rng))
+            .collect::<Vec<DVec3>>();
+
+        Perlin {
+            gradients,
+            permute_x: generate_permutation(rng),
+            permute_y: generate_permutation(rng),
+            permute_z: generate_permutation(rng),
+        }
+    }
+
+    // Smooth noise in [-1, 1]
+    pub fn noise(&self, point: DVec3) -> f64 {
+        let floor = point.floor();
+        let fraction = point - floor;
+        let (i, j, k) = (floor.x as i64, floor.y as i64, floor.z as i64);
+
+        let mut corners = [[[DVec3::ZERO; 2]; 2]; 2];
+        for (di, plane) in corners.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, corner) in row.iter_mut().enumerate() {
+                    let index = self.permute_x[((i + di as i64) & 255) as usize]
+                        ^ self.permute_y[((j + dj as i64) & 255) as usize]
+                        ^ self.permute_z[((k + dk as i64) & 255) as usize];
+                    *corner = self.gradients[index];
+                }
+            }
+        }
+
+        trilinear_interpolate(&corners, fraction)
+    }
+
+    // Sum of several octaves of noise, giving a marbled or cloudy look
+    pub fn turbulence(&self, point: DVec3, depth: u32) -> f64 {
+        let mut accumulated = 0.0;
+        let mut point = point;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accumulated += weight * self.noise(point);
+            weight *= 0.5;
+            point *= 2.0;
+        }
+
+        accumulated.abs()
+    }
+}
+
+fn generate_permutation(rng: &mut impl Rng) -> Vec<usize> {
+    let mut permutation = (0..POINT_COUNT).collect::<Vec<usize>>();
+    permutation.shuffle(rng);
+    permutation
+}
+
+fn trilinear_interpolate(corners: &[[[DVec3; 2]; 2]; 2], fraction: DVec3) -> f64 {
+    // Hermite smoothing removes grid artifacts
+    let smooth = fraction * fraction * (DVec3::splat(3.0) - 2.0 * fraction);
+
+    let mut accumulated = 0.0;
+    for (i, plane) in corners.iter().enumerate() {
+        for (j, row) in plane.iter().enumerate() {
+            for (k, gradient) in row.iter().enumerate() {
+                let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+                let weight = fraction - DVec3::new(fi, fj, fk);
+                accumulated += (fi * smooth.x + (1.0 - fi) * (1.0 - smooth.x))
+                    * (fj * smooth.y + (1.0 - fj) * (1.0 - smooth.y))
+                    * (fk * smooth.z + (1.0 - fk) * (1.0 - smooth.z))
+                    * gradient.dot(weight);
+            }
+        }
+    }
+
+    accumulated
+}
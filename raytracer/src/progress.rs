@@ -0,0 +1,113 @@
+use std::{fmt, sync::Arc};
+#[cfg(feature = "indicatif")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::{framebuffer::Framebuffer, tile::Tile};
+
+// Told how far a render has got, once per finished tile. Updates come from the render's worker
+// threads, so they can arrive slightly out of order.
+pub trait ProgressReporter: Send + Sync {
+    // `done` samples taken out of `total`, the samples the render takes if no pixel stops early
+    fn update(&self, done: u64, total: u64);
+
+    // A tile got its samples for the current pass. `image` holds the tile's pixels as they
+    // are now, before any denoising, ready to be drawn at (tile.x, tile.y).
+    fn tile_finished(&self, _tile: Tile, _image: &Framebuffer) {}
+
+    // The render finished, was cancelled or was stopped after a pass
+    fn finish(&self) {}
+}
+
+impl<F: Fn(u64, u64) + Send + Sync> ProgressReporter for F {
+    fn update(&self, done: u64, total: u64) {
+        self(done, total)
+    }
+}
+
+// Reporter stored in RenderSettings. Settings compare equal if they share the same reporter.
+#[derive(Default, Clone)]
+pub(crate) struct SharedReporter(Option<Arc<dyn ProgressReporter>>);
+
+impl SharedReporter {
+    pub(crate) fn new(reporter: impl ProgressReporter + 'static) -> Self {
+        SharedReporter(Some(Arc::new(reporter)))
+    }
+
+    pub(crate) fn update(&self, done: u64, total: u64) {
+        if let Some(reporter) = &self.0 {
+            reporter.update(done, total);
+        }
+    }
+
+    // `image` is only built when there is a reporter to show it to
+    pub(crate) fn tile_finished(&self, tile: Tile, image: impl FnOnce() -> Framebuffer) {
+        if let Some(reporter) = &self.0 {
+            reporter.tile_finished(tile, &image());
+        }
+    }
+
+    pub(crate) fn finish(&self) {
+        if let Some(reporter) = &self.0 {
+            reporter.finish();
+        }
+    }
+}
+
+impl fmt::Debug for SharedReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => f.write_str("Some(ProgressReporter)"),
+            None => f.write_str("None"),
+        }
+    }
+}
+
+impl PartialEq for SharedReporter {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+// Progress bar on the terminal
+#[cfg(feature = "indicatif")]
+pub struct IndicatifProgress {
+    bar: indicatif::ProgressBar,
+    // Furthest position reported, so late updates don't move the bar backwards
+    position: AtomicU64,
+}
+
+#[cfg(feature = "indicatif")]
+impl IndicatifProgress {
+    pub fn new() -> Self {
+        let bar = indicatif::ProgressBar::new(0);
+        bar.set_style(indicatif::ProgressStyle::default_bar());
+        IndicatifProgress {
+            bar,
+            position: AtomicU64::new(0),
+        }
+    }
+}
+
+#[cfg(feature = "indicatif")]
+impl Default for IndicatifProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "indicatif")]
+impl ProgressReporter for IndicatifProgress {
+    fn update(&self, done: u64, total: u64) {
+        // Moving by the difference keeps concurrent updates from undoing each other
+        let previous = self.position.fetch_max(done, Ordering::Relaxed);
+        self.bar.set_length(total);
+        self.bar.inc(done.saturating_sub(previous));
+    }
+
+    fn finish(&self) {
+        self.bar.finish();
+    }
+}
@@ -0,0 +1,216 @@
+use std::{io, path::Path};
+use glam::DVec3;
+use crate::{
+    aov::Aov,
+    camera::CropWindow,
+    color,
+    denoise::Denoiser,
+    film::Film,
+    framebuffer::Framebuffer,
+    progress::{ProgressReporter, SharedReporter},
+    stats::PathStats,
+    tile::TileOrder,
+};
+
+const DEFAULT_TILE_SIZE: usize = 32;
+
+// Where the render's worker threads come from
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Threads {
+    // Run on the rayon pool the caller is in, which is the global pool
+    // unless render is called from inside ThreadPool::install
+    #[default]
+    Current,
+    // Build a pool with this many threads just for the render, 0 meaning one per core. If the
+    // threads can't be spawned, the render runs on the current pool instead.
+    Count(usize),
+}
+
+// Options controlling how a render is carried out, as opposed to what the camera sees
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderSettings {
+    threads: Threads,
+    pub(crate) seed: u64,
+    pub(crate) aovs: Vec<Aov>,
+    pub(crate) denoiser: Option<Denoiser>,
+    pub(crate) progress: SharedReporter,
+    tile_size: usize,
+    pub(crate) tile_order: TileOrder,
+}
+
+impl RenderSettings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn threads(mut self, threads: Threads) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    // Renders with the same seed, scene and camera are bit-identical
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // Extra layers to accumulate next to the beauty image, in this order
+    pub fn aovs(mut self, aovs: impl IntoIterator<Item = Aov>) -> Self {
+        self.aovs = aovs.into_iter().collect();
+        self
+    }
+
+    // Denoise the finished image, guided by albedo and normal buffers gathered during the render.
+    // Only for full frames, not cameras with a crop window.
+    pub fn denoise(mut self, denoiser: Denoiser) -> Self {
+        self.denoiser = Some(denoiser);
+        self
+    }
+
+    // Render in square buckets of `size` pixels, handed out in `order`. 0 picks a default size.
+    pub fn tiles(mut self, size: usize, order: TileOrder) -> Self {
+        self.tile_size = size;
+        self.tile_order = order;
+        self
+    }
+
+    pub(crate) fn tile_size(&self) -> usize {
+        match self.tile_size {
+            0 => DEFAULT_TILE_SIZE,
+            size => size,
+        }
+    }
+
+    // Report progress while rendering, e.g. to progress::IndicatifProgress or a closure taking
+    // (done, total) samples. Renders are silent without one.
+    pub fn progress(mut self, reporter: impl ProgressReporter + 'static) -> Self {
+        self.progress = SharedReporter::new(reporter);
+        self
+    }
+
+    // AOVs to accumulate: the requested ones followed by any feature buffers the denoiser needs
+    pub(crate) fn accumulated_aovs(&self) -> Vec<Aov> {
+        let mut aovs = self.aovs.clone();
+        if self.denoiser.is_some() {
+            for feature in [Aov::Albedo, Aov::Normal] {
+                if !aovs.contains(&feature) {
+                    aovs.push(feature);
+                }
+            }
+        }
+        aovs
+    }
+
+    // Run a closure on the thread pool selected by these settings
+    pub(crate) fn install<R: Send>(&self, operation: impl FnOnce() -> R + Send) -> R {
+        match self.threads {
+            Threads::Current => operation(),
+            Threads::Count(count) => {
+                match rayon::ThreadPoolBuilder::new().num_threads(count).build() {
+                    Ok(pool) => pool.install(operation),
+                    // Could not spawn threads, so use whatever pool we are already on
+                    Err(_) => operation(),
+                }
+            }
+        }
+    }
+}
+
+// A pass of a progressive render that has just finished
+pub struct Pass<'a> {
+    pub(crate) number: u32,
+    pub(crate) film: &'a Film,
+    pub(crate) settings: &'a RenderSettings,
+}
+
+impl Pass<'_> {
+    // Counting from 1
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    // Everything accumulated so far, denoised if the settings ask for it. This averages the
+    // whole film, so only call it for passes that are actually going to be shown or saved.
+    pub fn output(&self) -> RenderOutput {
+        self.film.output(self.settings)
+    }
+}
+
+// Everything produced by one render
+#[derive(Debug, Clone)]
+pub struct RenderOutput {
+    pub image: Framebuffer,
+    // Requested AOVs, in the order given to RenderSettings::aovs
+    pub aovs: Vec<(Aov, Framebuffer)>,
+    // Samples taken in each pixel, which adaptive sampling can make less than samples_per_pixel
+    pub sample_counts: Vec<u32>,
+    pub stats: PathStats,
+    // Where the image sits in the camera's full frame, which is the whole frame unless
+    // the camera was given a crop window
+    pub crop: CropWindow,
+    pub frame_width: usize,
+    pub frame_height: usize,
+}
+
+impl RenderOutput {
+    pub fn aov(&self, aov: Aov) -> Option<&Framebuffer> {
+        self.aovs.iter()
+            .find(|(layer, _)| *layer == aov)
+            .map(|(_, framebuffer)| framebuffer)
+    }
+
+    // Sample counts as a heatmap, red where the most samples were taken
+    pub fn sample_count_heatmap(&self) -> Framebuffer {
+        let most = self.sample_counts.iter().copied().max().unwrap_or(0).max(1);
+        let pixels = self.sample_counts.iter()
+            .map(|&count| color::heatmap(count as f64 / most as f64))
+            .collect::<Vec<DVec3>>();
+
+        Framebuffer::from_pixels(self.image.width(), self.image.height(), pixels)
+    }
+
+    // OpenEXR with the beauty image in R, G, B and each AOV as a channel group named after it,
+    // e.g. albedo.R or depth.Z, which compositors show as separate layers. A crop is written
+    // as a data window inside the full frame's display window.
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        use exr::prelude::*;
+
+        let size = (self.image.width(), self.image.height());
+        let channel = |name: String, framebuffer: &Framebuffer, component: usize| {
+            let samples = framebuffer.pixels().iter()
+                .map(|pixel| pixel[component] as f32)
+                .collect::<Vec<f32>>();
+            AnyChannel::new(name.as_str(), FlatSamples::F32(samples))
+        };
+
+        let mut channels = ["R", "G", "B"].iter()
+            .enumerate()
+            .map(|(component, name)| channel(name.to_string(), &self.image, component))
+            .collect::<Vec<AnyChannel<FlatSamples>>>();
+
+        for (aov, framebuffer) in &self.aovs {
+            let names: &[&str] = match aov {
+                Aov::Depth => &["Z"],
+                Aov::Normal => &["X", "Y", "Z"],
+                _ => &["R", "G", "B"],
+            };
+            for (component, name) in names.iter().enumerate() {
+                channels.push(channel(format!("{}.{}", aov.name(), name), framebuffer, component));
+            }
+        }
+
+        let mut layer = Layer::new(
+            size,
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+        layer.attributes.layer_position = Vec2(self.crop.x as i32, self.crop.y as i32);
+        let frame = IntegerBounds::from_dimensions((self.frame_width, self.frame_height));
+
+        Image::new(ImageAttributes::new(frame), layer)
+            .write()
+            .to_file(path)
+            .map_err(io::Error::other)
+    }
+}
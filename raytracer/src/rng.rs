@@ -0,0 +1,25 @@
+use rand::SeedableRng;
+
+// Random number generation that gives the same sequence on every run and platform
+
+// Generator used for all sampling during a render
+pub type RenderRng = rand_pcg::Pcg64Mcg;
+
+// Independent generator for one sample of one pixel. Samples never share a stream,
+// so results do not depend on which thread renders which pixel or in what order.
+pub fn sample_rng(seed: u64, pixel: u64, sample: u64) -> RenderRng {
+    RenderRng::seed_from_u64(hash(&[seed, pixel, sample]))
+}
+
+// Combine several values into one well-mixed hash
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |hash, value| splitmix64(hash ^ value))
+}
+
+// Finaliser from the SplitMix64 generator, a cheap well-mixed 64-bit hash
+pub fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
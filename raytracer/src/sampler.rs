@@ -0,0 +1,488 @@
+use std::sync::OnceLock;
+use glam::DVec2;
+use rand::{Rng, SeedableRng};
+use crate::rng::{self, RenderRng};
+
+// Source of the uniform numbers used to build each camera path.
+// Every sample of a pixel asks for the same sequence of dimensions: pixel jitter,
+// then the lens, then one 2D and one 1D value per bounce. Samplers that know which
+// dimension they are generating can spread samples more evenly than independent random numbers.
+pub trait Sampler: Send {
+    // Reset to the first dimension of a new sample
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32);
+
+    // Next dimension, uniform in [0, 1)
+    fn get_1d(&mut self) -> f64;
+
+    // Next two dimensions, uniform in [0, 1)^2
+    fn get_2d(&mut self) -> DVec2;
+}
+
+// Which sampler a camera creates for each pixel
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SamplerKind {
+    // Uncorrelated random numbers
+    #[default]
+    Independent,
+    // Jittered strata, shuffled independently per dimension
+    Stratified,
+    // Halton sequence with randomly permuted digits
+    Halton,
+    // Owen-scrambled Sobol pairs with shuffled padding between dimensions
+    Sobol,
+    // Low-discrepancy sequence offset by a blue-noise mask, so error looks like fine grain
+    BlueNoise,
+}
+
+impl SamplerKind {
+    pub fn create(self, seed: u64, samples_per_pixel: u32) -> Box<dyn Sampler> {
+        match self {
+            SamplerKind::Independent => Box::new(IndependentSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(seed, samples_per_pixel)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+            SamplerKind::BlueNoise => Box::new(BlueNoiseSampler::new(seed)),
+        }
+    }
+}
+
+// Largest f64 below one, so samples never reach the end of [0, 1)
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// State shared by every sampler: which pixel, sample and dimension comes next
+struct SampleState {
+    seed: u64,
+    pixel: u64,
+    sample_index: u32,
+    dimension: u64,
+    // Independent numbers for jitter and for dimensions beyond a sequence's reach
+    rng: RenderRng,
+}
+
+impl SampleState {
+    fn new(seed: u64) -> Self {
+        SampleState {
+            seed,
+            pixel: 0,
+            sample_index: 0,
+            dimension: 0,
+            rng: RenderRng::seed_from_u64(seed),
+        }
+    }
+
+    fn start(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.pixel = ((y as u64) << 32) | x as u64;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+        self.rng = rng::sample_rng(self.seed, self.pixel, sample_index as u64);
+    }
+
+    // Advance past `count` dimensions, returning the first
+    fn take_dimensions(&mut self, count: u64) -> u64 {
+        let dimension = self.dimension;
+        self.dimension += count;
+        dimension
+    }
+
+    // Hash of the seed, pixel and a dimension, used to decorrelate pixels and dimensions
+    fn dimension_hash(&self, dimension: u64) -> u64 {
+        rng::hash(&[self.seed, self.pixel, dimension])
+    }
+}
+
+pub struct IndependentSampler {
+    state: SampleState,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> Self {
+        IndependentSampler { state: SampleState::new(seed) }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.state.rng.random()
+    }
+
+    fn get_2d(&mut self) -> DVec2 {
+        DVec2::new(self.state.rng.random(), self.state.rng.random())
+    }
+}
+
+// Divides each dimension (or pair of dimensions) into as many strata as there are samples,
+// then gives each sample a different stratum with a random offset inside it.
+pub struct StratifiedSampler {
+    state: SampleState,
+    samples_per_pixel: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: u32) -> Self {
+        StratifiedSampler {
+            state: SampleState::new(seed),
+            samples_per_pixel: samples_per_pixel.max(1),
+        }
+    }
+
+    // Stratum of the current sample among `count`, using a fresh shuffle for each dimension.
+    // Samples beyond the count start a new round with a new shuffle.
+    fn stratum(&self, dimension: u64, count: u32) -> u32 {
+        let index = self.state.sample_index;
+        let round = (index / count) as u64;
+        let shuffle = self.state.dimension_hash(dimension) ^ rng::splitmix64(round);
+        permutation_element(index % count, count, shuffle as u32)
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take_dimensions(1);
+        let stratum = self.stratum(dimension, self.samples_per_pixel);
+        let jitter: f64 = self.state.rng.random();
+        ((stratum as f64 + jitter) / self.samples_per_pixel as f64).min(ONE_MINUS_EPSILON)
+    }
+
+    fn get_2d(&mut self) -> DVec2 {
+        let dimension = self.state.take_dimensions(2);
+        // Smallest grid with at least one cell per sample
+        let columns = (self.samples_per_pixel as f64).sqrt().ceil() as u32;
+        let rows = self.samples_per_pixel.div_ceil(columns);
+
+        let stratum = self.stratum(dimension, columns * rows);
+        let jitter = DVec2::new(self.state.rng.random(), self.state.rng.random());
+        let cell = DVec2::new((stratum % columns) as f64, (stratum / columns) as f64);
+        ((cell + jitter) / DVec2::new(columns as f64, rows as f64)).min(DVec2::splat(ONE_MINUS_EPSILON))
+    }
+}
+
+const PRIMES: [u64; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+    137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193, 197, 199, 211, 223,
+    227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307, 311,
+];
+
+// Radical inverse in a different prime base per dimension. Each pixel applies its own random
+// permutation to every digit, which keeps the sequence's stratification while breaking up
+// both the correlation between pixels and between dimensions with large bases.
+// Dimensions past the prime table fall back to independent random numbers.
+pub struct HaltonSampler {
+    state: SampleState,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> Self {
+        HaltonSampler { state: SampleState::new(seed) }
+    }
+
+    fn sample_dimension(&mut self, dimension: u64) -> f64 {
+        match PRIMES.get(dimension as usize) {
+            Some(&base) => {
+                let permutation_seed = self.state.dimension_hash(dimension);
+                scrambled_radical_inverse(base, self.state.sample_index as u64, permutation_seed)
+            },
+            None => self.state.rng.random(),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take_dimensions(1);
+        self.sample_dimension(dimension)
+    }
+
+    fn get_2d(&mut self) -> DVec2 {
+        let dimension = self.state.take_dimensions(2);
+        DVec2::new(self.sample_dimension(dimension), self.sample_dimension(dimension + 1))
+    }
+}
+
+fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64) -> f64 {
+    let inverse_base = (base as f64).recip();
+    let mut digit_weight = inverse_base;
+    let mut result = 0.0;
+    let mut digit_position = 0;
+
+    // Keep going past the last non-zero digit, since permuted zeros contribute too
+    while index > 0 || digit_weight > 1e-10 {
+        let digit = (index % base) as u32;
+        let digit_seed = rng::hash(&[seed, digit_position]) as u32;
+        result += permutation_element(digit, base as u32, digit_seed) as f64 * digit_weight;
+
+        index /= base;
+        digit_weight *= inverse_base;
+        digit_position += 1;
+    }
+
+    result.min(ONE_MINUS_EPSILON)
+}
+
+// 2D Sobol points for each pair of dimensions (Burley, "Practical Hash-based Owen Scrambling", 2020).
+// The index is shuffled differently for every pair, so pairs do not correlate with each other,
+// and the points are Owen scrambled per pixel.
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> Self {
+        SobolSampler { state: SampleState::new(seed) }
+    }
+
+    fn shuffled_index(&self, dimension: u64) -> u32 {
+        let seed = self.state.dimension_hash(dimension) as u32;
+        nested_uniform_scramble(self.state.sample_index, seed)
+    }
+
+    fn scramble(&self, value: u32, dimension: u64) -> f64 {
+        let seed = rng::hash(&[self.state.seed, self.state.pixel, dimension, 1]) as u32;
+        (nested_uniform_scramble(value, seed) as f64 / 4294967296.0).min(ONE_MINUS_EPSILON)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take_dimensions(1);
+        let index = self.shuffled_index(dimension);
+        self.scramble(sobol_dimension_0(index), dimension)
+    }
+
+    fn get_2d(&mut self) -> DVec2 {
+        let dimension = self.state.take_dimensions(2);
+        let index = self.shuffled_index(dimension);
+        DVec2::new(
+            self.scramble(sobol_dimension_0(index), dimension),
+            self.scramble(sobol_dimension_1(index), dimension + 1),
+        )
+    }
+}
+
+// First Sobol dimension is the base 2 van der Corput sequence
+fn sobol_dimension_0(index: u32) -> u32 {
+    index.reverse_bits()
+}
+
+// Second Sobol dimension, from its direction numbers v_k = v_(k-1) ^ (v_(k-1) >> 1)
+fn sobol_dimension_1(mut index: u32) -> u32 {
+    let mut result = 0;
+    let mut direction = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= direction;
+        }
+        index >>= 1;
+        direction ^= direction >> 1;
+    }
+    result
+}
+
+// Hash that only lets bits affect bits above them, which is an Owen scramble after bit reversal
+fn laine_karras_permutation(mut value: u32, seed: u32) -> u32 {
+    value = value.wrapping_add(seed);
+    value ^= value.wrapping_mul(0x6c50b47c);
+    value ^= value.wrapping_mul(0xb82f1e52);
+    value ^= value.wrapping_mul(0xc7afe638);
+    value ^= value.wrapping_mul(0x8d22f6e6);
+    value
+}
+
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    laine_karras_permutation(value.reverse_bits(), seed).reverse_bits()
+}
+
+// Each dimension walks a rank-1 lattice (golden ratio in 1D, the R2 sequence in 2D) from a
+// starting point read out of a blue-noise mask, shifted by a different amount per dimension.
+// Neighbouring pixels then start far apart, pushing the remaining error to high frequencies.
+pub struct BlueNoiseSampler {
+    state: SampleState,
+}
+
+impl BlueNoiseSampler {
+    pub fn new(seed: u64) -> Self {
+        BlueNoiseSampler { state: SampleState::new(seed) }
+    }
+
+    fn mask_value(&self, dimension: u64) -> f64 {
+        let offset = self.state.dimension_hash(dimension);
+        let shift_x = offset as u32;
+        let shift_y = (offset >> 32) as u32;
+        let x = (self.state.pixel as u32).wrapping_add(shift_x);
+        let y = ((self.state.pixel >> 32) as u32).wrapping_add(shift_y);
+        blue_noise_mask(x, y)
+    }
+}
+
+impl Sampler for BlueNoiseSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let dimension = self.state.take_dimensions(1);
+        // Golden ratio conjugate
+        let step = 0.6180339887498949;
+        (self.mask_value(dimension) + self.state.sample_index as f64 * step).fract()
+    }
+
+    fn get_2d(&mut self) -> DVec2 {
+        let dimension = self.state.take_dimensions(2);
+        // R2 sequence steps, from the plastic number
+        let g: f64 = 1.324717957244746;
+        let step = DVec2::new(g.recip(), (g * g).recip());
+        let start = DVec2::new(self.mask_value(dimension), self.mask_value(dimension + 1));
+        (start + self.state.sample_index as f64 * step).fract()
+    }
+}
+
+// Kensler's hashed permutation ("Correlated Multi-Jittered Sampling", 2013):
+// element `index` of a random permutation of 0..length chosen by `seed`
+fn permutation_element(mut index: u32, length: u32, seed: u32) -> u32 {
+    let mut mask = length.wrapping_sub(1);
+    mask |= mask >> 1;
+    mask |= mask >> 2;
+    mask |= mask >> 4;
+    mask |= mask >> 8;
+    mask |= mask >> 16;
+
+    loop {
+        index ^= seed;
+        index = index.wrapping_mul(0xe170893d);
+        index ^= seed >> 16;
+        index ^= (index & mask) >> 4;
+        index ^= seed >> 8;
+        index = index.wrapping_mul(0x0929eb3f);
+        index ^= seed >> 23;
+        index ^= (index & mask) >> 1;
+        index = index.wrapping_mul(1 | seed >> 27);
+        index = index.wrapping_mul(0x6935fa69);
+        index ^= (index & mask) >> 11;
+        index = index.wrapping_mul(0x74dcb303);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0x9e501cc3);
+        index ^= (index & mask) >> 2;
+        index = index.wrapping_mul(0xc860a3df);
+        index &= mask;
+        index ^= index >> 5;
+        // Cycle walk until the result lands inside the range
+        if index < length {
+            break;
+        }
+    }
+
+    (index.wrapping_add(seed)) % length
+}
+
+const MASK_SIZE: usize = 64;
+
+// Tiling blue-noise mask value in [0, 1)
+fn blue_noise_mask(x: u32, y: u32) -> f64 {
+    static MASK: OnceLock<Vec<f64>> = OnceLock::new();
+    let mask = MASK.get_or_init(generate_blue_noise_mask);
+    let x = x as usize % MASK_SIZE;
+    let y = y as usize % MASK_SIZE;
+    mask[y * MASK_SIZE + x]
+}
+
+// Void and cluster (Ulichney, 1993): rank every cell so that the first n cells
+// of the ranking are always spread as evenly as possible
+fn generate_blue_noise_mask() -> Vec<f64> {
+    let count = MASK_SIZE * MASK_SIZE;
+    let sigma: f64 = 1.5;
+
+    // Toroidal Gaussian energy contributed by a point at offset (dx, dy)
+    let kernel = (0..count)
+        .map(|i| {
+            let wrap = |d: usize| {
+                let d = d as f64;
+                d.min(MASK_SIZE as f64 - d)
+            };
+            let dx = wrap(i % MASK_SIZE);
+            let dy = wrap(i / MASK_SIZE);
+            (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect::<Vec<f64>>();
+
+    let mut energy = vec![0.0; count];
+    let mut points = vec![false; count];
+
+    let toggle = |points: &mut Vec<bool>, energy: &mut Vec<f64>, cell: usize, on: bool| {
+        points[cell] = on;
+        let sign = if on { 1.0 } else { -1.0 };
+        let (cx, cy) = (cell % MASK_SIZE, cell / MASK_SIZE);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % MASK_SIZE + MASK_SIZE - cx) % MASK_SIZE;
+            let dy = (i / MASK_SIZE + MASK_SIZE - cy) % MASK_SIZE;
+            *e += sign * kernel[dy * MASK_SIZE + dx];
+        }
+    };
+    // Tightest cluster is the set point with the most energy, largest void the empty cell with least
+    let tightest_cluster = |points: &[bool], energy: &[f64]| {
+        (0..count).filter(|&i| points[i]).max_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+    let largest_void = |points: &[bool], energy: &[f64]| {
+        (0..count).filter(|&i| !points[i]).min_by(|&a, &b| energy[a].total_cmp(&energy[b])).unwrap()
+    };
+
+    // Initial random pattern covering a tenth of the cells
+    let mut rng = RenderRng::seed_from_u64(0);
+    let initial_count = count / 10;
+    let mut placed = 0;
+    while placed < initial_count {
+        let cell = rng.random_range(0..count);
+        if !points[cell] {
+            toggle(&mut points, &mut energy, cell, true);
+            placed += 1;
+        }
+    }
+
+    // Even it out by moving points from clusters into voids until nothing moves
+    for _ in 0..count {
+        let cluster = tightest_cluster(&points, &energy);
+        toggle(&mut points, &mut energy, cluster, false);
+        let void = largest_void(&points, &energy);
+        if void == cluster {
+            toggle(&mut points, &mut energy, cluster, true);
+            break;
+        }
+        toggle(&mut points, &mut energy, void, true);
+    }
+
+    let mut rank = vec![0usize; count];
+
+    // Rank the initial points by removing the tightest cluster each time
+    let (mut phase_points, mut phase_energy) = (points.clone(), energy.clone());
+    for r in (0..initial_count).rev() {
+        let cluster = tightest_cluster(&phase_points, &phase_energy);
+        toggle(&mut phase_points, &mut phase_energy, cluster, false);
+        rank[cluster] = r;
+    }
+
+    // Then rank the rest by filling the largest void each time
+    for r in initial_count..count {
+        let void = largest_void(&points, &energy);
+        toggle(&mut points, &mut energy, void, true);
+        rank[void] = r;
+    }
+
+    rank.into_iter()
+        .map(|r| (r as f64 + 0.5) / count as f64)
+        .collect()
+}
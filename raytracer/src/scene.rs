@@ -0,0 +1,37 @@
+use std::{ops::Range, sync::Arc};
+use crate::{
+    environment::Environment,
+    hittable::{HitRecord, Hittable, HittableList},
+    ray::Ray3,
+    stats,
+};
+
+// Everything a ray can interact with during a render
+pub struct Scene {
+    pub world: Arc<dyn Hittable>,
+    // Emissive objects to aim shadow rays at. Usually copies of the lights already in `world`,
+    // since only their shape is used; the emission still comes from whatever the shadow ray hits.
+    pub lights: HittableList,
+    pub environment: Environment,
+}
+
+impl Scene {
+    pub fn new(world: Arc<dyn Hittable>, environment: Environment) -> Scene {
+        Scene {
+            world,
+            lights: HittableList::new(),
+            environment,
+        }
+    }
+
+    pub fn with_lights(mut self, lights: HittableList) -> Scene {
+        self.lights = lights;
+        self
+    }
+
+    // Closest hit in the world. Integrators cast every ray through here so they can be counted.
+    pub fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
+        stats::count_ray();
+        self.world.hit(ray, interval)
+    }
+}
@@ -1,9 +1,13 @@
 use std::ops::Range;
-use glam::DVec3;
+use std::f64::consts::PI;
+use glam::{DVec2, DVec3};
 use crate::{
+    aabb::Aabb,
     hittable::{HitRecord, Hittable},
     material::Material,
-    ray::Ray3
+    ray::Ray3,
+    stats,
+    vector_utils::{self, Onb},
 };
 
 // #[derive(Debug, Default)]
@@ -21,10 +25,22 @@ impl Sphere {
             material,
         }
     }
+
+    // Cosine of the half-angle of the cone the sphere subtends, None from inside the sphere
+    fn cos_theta_max(&self, origin: DVec3) -> Option<f64> {
+        let distance_squared = (self.center - origin).length_squared();
+        let radius_squared = self.radius * self.radius;
+        if distance_squared <= radius_squared {
+            return None;
+        }
+
+        Some((1.0 - radius_squared / distance_squared).sqrt())
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord> {
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
+        stats::count_primitive_test();
         let vect_oc = self.center - ray.origin;
         let a = ray.direction.length_squared();
         let h = ray.direction.dot(vect_oc);
@@ -53,8 +69,45 @@ impl Hittable for Sphere {
         let outward_normal = ( point - self.center) / self.radius;
         // record.set_face_normal(ray, outward_normal);
 
-        let record = HitRecord::with_face_normal(point, outward_normal, t, self.material, ray);
+        let record = HitRecord::with_face_normal(point, outward_normal, t, &self.material, ray)
+            .with_uv(sphere_uv(outward_normal));
 
         Some(record)
     }
+
+    fn bounding_box(&self) -> Aabb {
+        let radius_vector = DVec3::splat(self.radius);
+        Aabb::new(self.center - radius_vector, self.center + radius_vector)
+    }
+
+    // Uniform over the cone of directions the sphere covers
+    fn pdf_value(&self, origin: DVec3, direction: DVec3) -> f64 {
+        let Some(cos_theta_max) = self.cos_theta_max(origin) else {
+            return 0.0;
+        };
+        if self.hit(Ray3::new(origin, direction), 0.001..f64::INFINITY).is_none() {
+            return 0.0;
+        }
+
+        1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+    }
+
+    fn random(&self, origin: DVec3, u: DVec2) -> DVec3 {
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => {
+                let to_center = (self.center - origin).normalize();
+                Onb::new(to_center).local(vector_utils::sample_cone(u, cos_theta_max))
+            },
+            // From inside, every direction reaches the sphere
+            None => vector_utils::sample_unit_vector(u),
+        }
+    }
+}
+
+// Map a point on the unit sphere to (u, v), with u wrapping around the y axis from -x
+// and v running from the bottom pole to the top
+fn sphere_uv(point: DVec3) -> DVec2 {
+    let theta = (-point.y).clamp(-1.0, 1.0).acos();
+    let phi = (-point.z).atan2(point.x) + PI;
+    DVec2::new(phi / (2.0 * PI), theta / PI)
 }
\ No newline at end of file
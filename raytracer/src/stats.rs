@@ -0,0 +1,198 @@
+use std::{cell::Cell, fmt};
+
+// Why a path stopped bouncing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PathEnd {
+    // Left the scene and picked up the environment
+    Escaped,
+    // Hit a surface that doesn't scatter, or whose scattering carries no light
+    Absorbed,
+    // Killed by Russian roulette
+    Roulette,
+    // Reached the camera's maximum depth
+    MaxDepth,
+}
+
+// Path lengths over a whole render, where length counts the surfaces a path hit
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PathStats {
+    // Number of paths of each length, indexed by length
+    pub lengths: Vec<u64>,
+    pub escaped: u64,
+    pub absorbed: u64,
+    pub roulette: u64,
+    pub max_depth: u64,
+}
+
+impl PathStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, length: u32, end: PathEnd) {
+        let length = length as usize;
+        if self.lengths.len() <= length {
+            self.lengths.resize(length + 1, 0);
+        }
+        self.lengths[length] += 1;
+
+        match end {
+            PathEnd::Escaped => self.escaped += 1,
+            PathEnd::Absorbed => self.absorbed += 1,
+            PathEnd::Roulette => self.roulette += 1,
+            PathEnd::MaxDepth => self.max_depth += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &PathStats) {
+        if self.lengths.len() < other.lengths.len() {
+            self.lengths.resize(other.lengths.len(), 0);
+        }
+        for (count, other_count) in self.lengths.iter_mut().zip(&other.lengths) {
+            *count += other_count;
+        }
+
+        self.escaped += other.escaped;
+        self.absorbed += other.absorbed;
+        self.roulette += other.roulette;
+        self.max_depth += other.max_depth;
+    }
+
+    pub fn paths(&self) -> u64 {
+        self.lengths.iter().sum()
+    }
+
+    // Surfaces hit by all paths together
+    pub fn bounces(&self) -> u64 {
+        self.lengths.iter()
+            .enumerate()
+            .map(|(length, count)| length as u64 * count)
+            .sum()
+    }
+
+    pub fn mean_length(&self) -> f64 {
+        match self.paths() {
+            0 => 0.0,
+            paths => self.bounces() as f64 / paths as f64,
+        }
+    }
+
+    pub fn longest(&self) -> usize {
+        self.lengths.iter().rposition(|&count| count > 0).unwrap_or(0)
+    }
+}
+
+impl fmt::Display for PathStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} paths, mean length {:.2}, longest {}", self.paths(), self.mean_length(), self.longest())?;
+        write!(
+            f,
+            "ended by escaping {}, absorption {}, russian roulette {}, max depth {}",
+            self.escaped, self.absorbed, self.roulette, self.max_depth,
+        )
+    }
+}
+
+// Running mean and variance of a stream of values (Welford's algorithm)
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct RunningStats {
+    count: u32,
+    mean: f64,
+    // Sum of squared differences from the mean
+    m2: f64,
+}
+
+impl RunningStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Count, mean and sum of squared differences, for saving and restoring exactly
+    pub(crate) fn parts(&self) -> (u32, f64, f64) {
+        (self.count, self.mean, self.m2)
+    }
+
+    pub(crate) fn from_parts(count: u32, mean: f64, m2: f64) -> Self {
+        RunningStats { count, mean, m2 }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    // Unbiased sample variance
+    pub fn variance(&self) -> f64 {
+        match self.count {
+            0 | 1 => 0.0,
+            count => self.m2 / (count - 1) as f64,
+        }
+    }
+
+    // Estimated standard deviation of the mean itself
+    pub fn standard_error(&self) -> f64 {
+        match self.count {
+            0 => 0.0,
+            count => (self.variance() / count as f64).sqrt(),
+        }
+    }
+}
+
+// Intersection tests run so far on the current thread
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct IntersectionCounts {
+    // Rays cast into the scene through Scene::hit
+    pub rays: u64,
+    // Ray/primitive tests (spheres and triangles), only counted with the intersection-stats feature
+    pub primitives: u64,
+    // Ray/box tests against BVH nodes, only counted with the intersection-stats feature
+    pub boxes: u64,
+}
+
+thread_local! {
+    static INTERSECTION_COUNTS: Cell<IntersectionCounts> = const {
+        Cell::new(IntersectionCounts { rays: 0, primitives: 0, boxes: 0 })
+    };
+}
+
+// Take the difference of two readings to count the tests done by a single ray
+pub fn intersection_counts() -> IntersectionCounts {
+    INTERSECTION_COUNTS.with(|counts| counts.get())
+}
+
+pub(crate) fn count_ray() {
+    INTERSECTION_COUNTS.with(|counts| {
+        let mut current = counts.get();
+        current.rays += 1;
+        counts.set(current);
+    });
+}
+
+// The test counters sit in the innermost loops, so they cost nothing unless asked for
+pub(crate) fn count_primitive_test() {
+    #[cfg(feature = "intersection-stats")]
+    INTERSECTION_COUNTS.with(|counts| {
+        let mut current = counts.get();
+        current.primitives += 1;
+        counts.set(current);
+    });
+}
+
+pub(crate) fn count_box_test() {
+    #[cfg(feature = "intersection-stats")]
+    INTERSECTION_COUNTS.with(|counts| {
+        let mut current = counts.get();
+        current.boxes += 1;
+        counts.set(current);
+    });
+}
@@ -0,0 +1,154 @@
+use std::{io, path::Path, sync::Arc};
+use glam::{DVec2, DVec3};
+use rand::Rng;
+use crate::{color, perlin::Perlin};
+
+// Spatially varying color, sampled by surface (u, v) coordinates and hit point
+#[derive(Clone)]
+pub enum Texture {
+    Solid(DVec3),
+    // Alternating 3D cells of two textures, `scale` is the cell size in world units
+    Checker {
+        scale: f64,
+        even: Box<Texture>,
+        odd: Box<Texture>,
+    },
+    Image(Arc<ImageTexture>),
+    Noise {
+        noise: Arc<Perlin>,
+        // Frequency of the noise in world space
+        scale: f64,
+        style: NoiseStyle,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NoiseStyle {
+    // Plain gradient noise
+    Smooth,
+    // Several octaves summed together
+    Turbulence { depth: u32 },
+    // Sine bands along z, phase shifted by turbulence
+    Marble { depth: u32 },
+}
+
+impl From<DVec3> for Texture {
+    fn from(color: DVec3) -> Self {
+        Texture::Solid(color)
+    }
+}
+
+impl Texture {
+    pub fn checker(scale: f64, even: impl Into<Texture>, odd: impl Into<Texture>) -> Texture {
+        Texture::Checker {
+            scale,
+            even: Box::new(even.into()),
+            odd: Box::new(odd.into()),
+        }
+    }
+
+    // Noise with its own pattern drawn from `rng`, so a scene built from a seeded RNG comes out
+    // the same every time without all its noise textures looking alike
+    pub fn noise(scale: f64, style: NoiseStyle, rng: &mut impl Rng) -> Texture {
+        Texture::Noise {
+            noise: Arc::new(Perlin::new(rng)),
+            scale,
+            style,
+        }
+    }
+
+    pub fn image(path: impl AsRef<Path>) -> io::Result<Texture> {
+        ImageTexture::load(path).map(|image| Texture::Image(Arc::new(image)))
+    }
+
+    pub fn value(&self, uv: DVec2, point: DVec3) -> DVec3 {
+        match self {
+            Texture::Solid(color) => *color,
+
+            Texture::Checker { scale, even, odd } => {
+                let cell = (point / *scale).floor();
+                let sum = cell.x as i64 + cell.y as i64 + cell.z as i64;
+                match sum.rem_euclid(2) == 0 {
+                    true => even.value(uv, point),
+                    false => odd.value(uv, point),
+                }
+            },
+
+            Texture::Image(image) => image.sample(uv),
+
+            Texture::Noise { noise, scale, style } => {
+                let scaled = *scale * point;
+                let intensity = match *style {
+                    NoiseStyle::Smooth => 0.5 * (1.0 + noise.noise(scaled)),
+                    NoiseStyle::Turbulence { depth } => noise.turbulence(scaled, depth),
+                    NoiseStyle::Marble { depth } => {
+                        0.5 * (1.0 + (scaled.z + 10.0 * noise.turbulence(point, depth)).sin())
+                    },
+                };
+                DVec3::splat(intensity)
+            },
+        }
+    }
+}
+
+// Bitmap texture, stored as linear RGB
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<DVec3>,
+}
+
+impl ImageTexture {
+    // Load any 8-bit format supported by the image crate, decoding sRGB to linear
+    pub fn load(path: impl AsRef<Path>) -> io::Result<ImageTexture> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?
+            .into_rgb8();
+
+        let pixels = image.pixels()
+            .map(|pixel| DVec3::new(
+                color::srgb_to_linear(pixel[0] as f64 / 255.0),
+                color::srgb_to_linear(pixel[1] as f64 / 255.0),
+                color::srgb_to_linear(pixel[2] as f64 / 255.0),
+            ))
+            .collect::<Vec<DVec3>>();
+
+        Ok(ImageTexture {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels,
+        })
+    }
+
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<DVec3>) -> ImageTexture {
+        assert_eq!(pixels.len(), width * height, "image texture needs width * height pixels");
+        ImageTexture { width, height, pixels }
+    }
+
+    // Bilinear lookup with repeating wrap. v = 0 is the bottom row of the image.
+    pub fn sample(&self, uv: DVec2) -> DVec3 {
+        if self.pixels.is_empty() {
+            // Solid cyan as a debugging aid
+            return DVec3::new(0.0, 1.0, 1.0);
+        }
+
+        let x = uv.x.rem_euclid(1.0) * self.width as f64 - 0.5;
+        let y = (1.0 - uv.y.rem_euclid(1.0)) * self.height as f64 - 0.5;
+
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let texel = |i: f64, j: f64| {
+            let i = (i as i64).rem_euclid(self.width as i64) as usize;
+            let j = (j as i64).rem_euclid(self.height as i64) as usize;
+            self.pixels[j * self.width + i]
+        };
+
+        let top = texel(x0, y0).lerp(texel(x0 + 1.0, y0), tx);
+        let bottom = texel(x0, y0 + 1.0).lerp(texel(x0 + 1.0, y0 + 1.0), tx);
+        top.lerp(bottom, ty)
+    }
+}
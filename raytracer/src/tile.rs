@@ -0,0 +1,122 @@
+use std::cmp::Ordering;
+
+// Rectangle of pixels rendered together as one bucket
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Tile {
+    // Top left pixel
+    pub x: usize,
+    pub y: usize,
+    // Smaller than the tile size along the right and bottom edges of the image
+    pub width: usize,
+    pub height: usize,
+}
+
+// Order in which tiles are handed to worker threads
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TileOrder {
+    // Row by row from the top left
+    #[default]
+    Scanline,
+    // Outwards from the center of the image, where the subject usually is
+    Spiral,
+    // Along a Hilbert curve, so consecutive tiles are nearly always neighbours
+    Hilbert,
+}
+
+// Cover a width x height image with tiles of `size` pixels square, listed in the given order
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    let tile = |column: usize, row: usize| Tile {
+        x: column * size,
+        y: row * size,
+        width: size.min(width - column * size),
+        height: size.min(height - row * size),
+    };
+
+    let mut cells = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect::<Vec<(usize, usize)>>();
+
+    match order {
+        TileOrder::Scanline => {},
+        TileOrder::Spiral => {
+            // Ring by ring around the center tile, going round each ring by angle
+            let center_x = (columns as f64 - 1.0) / 2.0;
+            let center_y = (rows as f64 - 1.0) / 2.0;
+            let key = |&(column, row): &(usize, usize)| {
+                let dx = column as f64 - center_x;
+                let dy = row as f64 - center_y;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+        },
+        TileOrder::Hilbert => {
+            // Walk the curve over the smallest power of two square covering the grid, skipping cells outside it
+            let side = columns.max(rows).next_power_of_two();
+            cells = (0..side * side)
+                .map(|distance| hilbert_cell(side, distance))
+                .filter(|&(column, row)| column < columns && row < rows)
+                .collect();
+        },
+    }
+
+    cells.into_iter()
+        .map(|(column, row)| tile(column, row))
+        .collect()
+}
+
+// Cell at `distance` along a Hilbert curve filling a side x side grid, side being a power of two
+fn hilbert_cell(side: usize, distance: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = distance;
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        // Rotate the quadrant so the curve joins up
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tiles, TileOrder};
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert];
+    // Sizes that don't divide evenly into tiles, wide and tall, and smaller than one tile
+    const SIZES: [(usize, usize); 5] = [(13, 7), (7, 13), (32, 5), (3, 2), (16, 16)];
+
+    #[test]
+    fn every_pixel_is_covered_once() {
+        for order in ORDERS {
+            for (width, height) in SIZES {
+                for size in [1, 4, 5] {
+                    let mut covered = vec![0; width * height];
+                    for tile in tiles(width, height, size, order) {
+                        assert!(tile.width > 0 && tile.height > 0);
+                        for y in tile.y..tile.y + tile.height {
+                            for x in tile.x..tile.x + tile.width {
+                                covered[y * width + x] += 1;
+                            }
+                        }
+                    }
+                    assert!(covered.iter().all(|&count| count == 1), "{order:?} {width}x{height} in tiles of {size}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use glam::{DMat3, DVec3};
+use crate::color;
+
+// Operators compressing scene radiance into the displayable [0, 1] range
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum ToneMap {
+    // Clip each channel at 1
+    #[default]
+    Clamp,
+    // L / (1 + L) on luminance, preserving hue
+    Reinhard,
+    // Reinhard with a luminance that maps to pure white
+    ExtendedReinhard {
+        white_point: f64,
+    },
+    // Stephen Hill's fit of the ACES reference rendering and output transforms
+    AcesFilmic,
+    // Minimal AgX approximation, desaturating bright colors more gracefully than ACES
+    AgX,
+}
+
+impl ToneMap {
+    // Map linear radiance to linear display values in [0, 1]
+    pub fn apply(&self, color: DVec3) -> DVec3 {
+        let color = color.max(DVec3::ZERO);
+        let mapped = match *self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard { white_point } => {
+                let white_squared = (white_point * white_point).max(f64::MIN_POSITIVE);
+                scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            },
+            ToneMap::AcesFilmic => aces_filmic(color),
+            ToneMap::AgX => agx(color),
+        };
+        mapped.clamp(DVec3::ZERO, DVec3::ONE)
+    }
+}
+
+// Everything needed to turn a linear framebuffer into an 8-bit image
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct DisplayTransform {
+    tone_map: ToneMap,
+    // Exposure adjustment in stops, applied before tone mapping
+    exposure: f64,
+}
+
+impl DisplayTransform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    pub fn exposure(mut self, stops: f64) -> Self {
+        self.exposure = stops;
+        self
+    }
+
+    // Linear radiance to sRGB encoded values in [0, 1]
+    pub fn apply(&self, color: DVec3) -> DVec3 {
+        let exposed = color * self.exposure.exp2();
+        let display = self.tone_map.apply(exposed);
+        DVec3::new(
+            color::linear_to_srgb(display.x),
+            color::linear_to_srgb(display.y),
+            color::linear_to_srgb(display.z),
+        )
+    }
+
+    pub fn to_8bit(&self, color: DVec3) -> [u8; 3] {
+        let encoded = (self.apply(color) * 255.0).round();
+        [encoded.x as u8, encoded.y as u8, encoded.z as u8]
+    }
+}
+
+fn scale_luminance(color: DVec3, curve: impl Fn(f64) -> f64) -> DVec3 {
+    let luminance = color::luminance(color);
+    if luminance <= 0.0 {
+        return DVec3::ZERO;
+    }
+    color * (curve(luminance) / luminance)
+}
+
+fn aces_filmic(color: DVec3) -> DVec3 {
+    // sRGB to ACES fitted space, including the RRT saturation adjustment
+    let input = DMat3::from_cols_array(&[
+        0.59719, 0.35458, 0.04823,
+        0.07600, 0.90834, 0.01566,
+        0.02840, 0.13383, 0.83777,
+    ]).transpose();
+    // And back again, including the ODT saturation adjustment
+    let output = DMat3::from_cols_array(&[
+        1.60475, -0.53108, -0.07367,
+        -0.10208, 1.10813, -0.00605,
+        -0.00327, -0.07276, 1.07602,
+    ]).transpose();
+
+    let v = input * color;
+    let a = v * (v + 0.0245786) - 0.000090537;
+    let b = v * (0.983729 * v + 0.4329510) + 0.238081;
+    output * (a / b)
+}
+
+fn agx(color: DVec3) -> DVec3 {
+    let inset = DMat3::from_cols_array(&[
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+    ]);
+    let outset = DMat3::from_cols_array(&[
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+    ]);
+    let min_ev = -12.47393;
+    let max_ev = 4.026069;
+
+    // Log encode into [0, 1] over the covered range of stops
+    let v = inset * color;
+    let v = v.max(DVec3::splat(1e-10));
+    let v = DVec3::new(v.x.log2(), v.y.log2(), v.z.log2());
+    let x = ((v - min_ev) / (max_ev - min_ev)).clamp(DVec3::ZERO, DVec3::ONE);
+
+    // Polynomial fit of the AgX base sigmoid
+    let x2 = x * x;
+    let x4 = x2 * x2;
+    let curve = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+
+    // The curve outputs display encoded values, so decode them back to linear
+    let encoded = (outset * curve).max(DVec3::ZERO);
+    DVec3::new(encoded.x.powf(2.2), encoded.y.powf(2.2), encoded.z.powf(2.2))
+}
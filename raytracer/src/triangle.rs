@@ -0,0 +1,343 @@
+use std::{ops::Range, sync::Arc};
+use glam::{DVec2, DVec3};
+use crate::{
+    aabb::Aabb,
+    bvh::{Bvh, SplitMethod},
+    hittable::{HitRecord, Hittable},
+    material::Material,
+    ray::Ray3,
+    stats,
+};
+
+pub struct Triangle {
+    vertices: [DVec3; 3],
+    normals: Option<[DVec3; 3]>,
+    uvs: Option<[DVec2; 3]>,
+    material: Material,
+}
+
+impl Triangle {
+    pub fn new(a: DVec3, b: DVec3, c: DVec3, material: Material) -> Triangle {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    // Per-vertex shading normals, interpolated across the face
+    pub fn with_normals(mut self, normals: [DVec3; 3]) -> Triangle {
+        self.normals = Some(normals);
+        self
+    }
+
+    // Per-vertex texture coordinates, otherwise the barycentrics are used as (u, v)
+    pub fn with_uvs(mut self, uvs: [DVec2; 3]) -> Triangle {
+        self.uvs = Some(uvs);
+        self
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
+        intersect(self.vertices, ray, interval).map(|(t, barycentric)| {
+            let attributes = VertexAttributes { normals: self.normals, uvs: self.uvs };
+            triangle_record(self.vertices, attributes, t, barycentric, &self.material, ray)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.vertices;
+        Aabb::new(a, b).including(c)
+    }
+
+    fn pdf_value(&self, origin: DVec3, direction: DVec3) -> f64 {
+        match self.hit(Ray3::new(origin, direction), 0.001..f64::INFINITY) {
+            Some(record) => solid_angle_pdf(direction, &record, area(self.vertices).recip()),
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: DVec3, u: DVec2) -> DVec3 {
+        sample_point(self.vertices, u) - origin
+    }
+}
+
+// Vertex and index buffers shared by every triangle of a mesh
+#[derive(Debug, Default, Clone)]
+pub struct MeshData {
+    pub positions: Vec<DVec3>,
+    // Per-vertex shading normals, left empty for flat shading
+    pub normals: Vec<DVec3>,
+    // Per-vertex texture coordinates, may be left empty
+    pub uvs: Vec<DVec2>,
+    // Three indices into the vertex buffers per triangle
+    pub indices: Vec<[u32; 3]>,
+}
+
+// Indexed triangle mesh with its own BVH over the faces
+pub struct TriangleMesh {
+    bvh: Bvh<MeshTriangle>,
+    mesh: Arc<MeshShared>,
+    // Running total of triangle areas, for picking faces when the mesh is sampled as a light
+    area_cdf: Vec<f64>,
+}
+
+struct MeshShared {
+    data: MeshData,
+    material: Material,
+}
+
+// One face of a mesh, referring back to the shared buffers
+struct MeshTriangle {
+    mesh: Arc<MeshShared>,
+    index: usize,
+}
+
+impl TriangleMesh {
+    pub fn new(data: MeshData, material: Material) -> TriangleMesh {
+        let vertex_count = data.positions.len();
+        assert!(
+            data.normals.is_empty() || data.normals.len() == vertex_count,
+            "mesh has {} normals for {} vertices", data.normals.len(), vertex_count
+        );
+        assert!(
+            data.uvs.is_empty() || data.uvs.len() == vertex_count,
+            "mesh has {} texture coordinates for {} vertices", data.uvs.len(), vertex_count
+        );
+        assert!(
+            data.indices.iter().flatten().all(|&i| (i as usize) < vertex_count),
+            "mesh index out of range of {} vertices", vertex_count
+        );
+
+        let triangle_count = data.indices.len();
+        let mesh = Arc::new(MeshShared { data, material });
+        let triangles = (0..triangle_count)
+            .map(|index| MeshTriangle { mesh: Arc::clone(&mesh), index })
+            .collect::<Vec<MeshTriangle>>();
+
+        let area_cdf = triangles.iter()
+            .scan(0.0, |total, triangle| {
+                *total += area(triangle.vertices());
+                Some(*total)
+            })
+            .collect::<Vec<f64>>();
+
+        TriangleMesh {
+            bvh: Bvh::from_objects(triangles, SplitMethod::Sah),
+            mesh,
+            area_cdf,
+        }
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        self.area_cdf.last().copied().unwrap_or(0.0)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.bvh.len()
+    }
+
+    pub fn data(&self) -> &MeshData {
+        &self.mesh.data
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
+        self.bvh.hit(ray, interval)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    // Uniform over the surface area of the whole mesh. Every face along the direction could have
+    // been sampled, including ones hidden behind the nearest, so their densities add up.
+    fn pdf_value(&self, origin: DVec3, direction: DVec3) -> f64 {
+        let surface_area = self.surface_area();
+        if surface_area <= 0.0 {
+            return 0.0;
+        }
+
+        let ray = Ray3::new(origin, direction);
+        let mut pdf = 0.0;
+        let mut start = 0.001;
+        for _ in 0..self.triangle_count() {
+            let Some(record) = self.hit(ray, start..f64::INFINITY) else {
+                break;
+            };
+            pdf += solid_angle_pdf(direction, &record, surface_area.recip());
+            start = record.t + 0.001;
+        }
+        pdf
+    }
+
+    fn random(&self, origin: DVec3, u: DVec2) -> DVec3 {
+        let surface_area = self.surface_area();
+        if surface_area <= 0.0 {
+            return DVec3::X;
+        }
+
+        // Pick a face in proportion to its area, then reuse the rest of the first dimension
+        let target = u.x * surface_area;
+        let index = self.area_cdf.partition_point(|&total| total <= target).min(self.area_cdf.len() - 1);
+        let start = if index == 0 { 0.0 } else { self.area_cdf[index - 1] };
+        let face_area = self.area_cdf[index] - start;
+        let remapped = ((target - start) / face_area).clamp(0.0, 1.0);
+
+        let triangle = MeshTriangle { mesh: Arc::clone(&self.mesh), index };
+        sample_point(triangle.vertices(), DVec2::new(remapped, u.y)) - origin
+    }
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> [DVec3; 3] {
+        let positions = &self.mesh.data.positions;
+        self.mesh.data.indices[self.index].map(|i| positions[i as usize])
+    }
+
+    fn attributes(&self) -> VertexAttributes {
+        let data = &self.mesh.data;
+        let indices = data.indices[self.index];
+        VertexAttributes {
+            normals: (!data.normals.is_empty()).then(|| indices.map(|i| data.normals[i as usize])),
+            uvs: (!data.uvs.is_empty()).then(|| indices.map(|i| data.uvs[i as usize])),
+        }
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: Ray3, interval: Range<f64>) -> Option<HitRecord<'_>> {
+        let vertices = self.vertices();
+        intersect(vertices, ray, interval).map(|(t, barycentric)| {
+            triangle_record(vertices, self.attributes(), t, barycentric, &self.mesh.material, ray)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.vertices();
+        Aabb::new(a, b).including(c)
+    }
+}
+
+// Optional per-vertex data for a single triangle
+struct VertexAttributes {
+    normals: Option<[DVec3; 3]>,
+    uvs: Option<[DVec2; 3]>,
+}
+
+fn triangle_record(vertices: [DVec3; 3], attributes: VertexAttributes, t: f64, barycentric: DVec2, material: &Material, ray: Ray3) -> HitRecord<'_> {
+    let [a, b, c] = vertices;
+    let w = 1.0 - barycentric.x - barycentric.y;
+    let uv = match attributes.uvs {
+        Some([ta, tb, tc]) => w * ta + barycentric.x * tb + barycentric.y * tc,
+        None => barycentric,
+    };
+
+    let geometric_normal = (b - a).cross(c - a).normalize();
+    let record = HitRecord::with_face_normal(ray.at(t), geometric_normal, t, material, ray)
+        .with_barycentric(barycentric)
+        .with_uv(uv);
+
+    match attributes.normals {
+        Some([na, nb, nc]) => {
+            let interpolated = (w * na + barycentric.x * nb + barycentric.y * nc).normalize_or_zero();
+            if interpolated == DVec3::ZERO {
+                record
+            } else {
+                record.with_shading_normal(interpolated)
+            }
+        },
+        None => record,
+    }
+}
+
+fn area(vertices: [DVec3; 3]) -> f64 {
+    let [a, b, c] = vertices;
+    0.5 * (b - a).cross(c - a).length()
+}
+
+// Uniformly distributed point on the triangle
+fn sample_point(vertices: [DVec3; 3], u: DVec2) -> DVec3 {
+    let [a, b, c] = vertices;
+    let root = u.x.sqrt();
+    (1.0 - root) * a + root * (1.0 - u.y) * b + root * u.y * c
+}
+
+// Convert a density over surface area at the hit point to one over solid angle at the ray origin
+fn solid_angle_pdf(direction: DVec3, record: &HitRecord, area_pdf: f64) -> f64 {
+    let distance_squared = (record.t * direction).length_squared();
+    let cosine = direction.normalize().dot(record.geometric_normal).abs();
+    if cosine <= 0.0 {
+        return 0.0;
+    }
+
+    area_pdf * distance_squared / cosine
+}
+
+// Watertight ray/triangle intersection (Woop, Benthin and Wald, 2013).
+// Rays passing exactly through a shared edge or vertex hit at least one of the adjoining triangles.
+// Returns the ray parameter and the barycentric weights of vertices b and c.
+fn intersect(vertices: [DVec3; 3], ray: Ray3, interval: Range<f64>) -> Option<(f64, DVec2)> {
+    stats::count_primitive_test();
+    let [a, b, c] = vertices;
+    let direction = ray.direction;
+
+    // Permute axes so z is the dominant direction axis, keeping the winding
+    let abs_direction = direction.abs();
+    let kz = if abs_direction.x > abs_direction.y && abs_direction.x > abs_direction.z {
+        0
+    } else if abs_direction.y > abs_direction.z {
+        1
+    } else {
+        2
+    };
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if direction[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+
+    // Shear so the ray points down +z
+    let shear_x = direction[kx] / direction[kz];
+    let shear_y = direction[ky] / direction[kz];
+    let shear_z = direction[kz].recip();
+
+    let va = a - ray.origin;
+    let vb = b - ray.origin;
+    let vc = c - ray.origin;
+
+    let ax = va[kx] - shear_x * va[kz];
+    let ay = va[ky] - shear_y * va[kz];
+    let bx = vb[kx] - shear_x * vb[kz];
+    let by = vb[ky] - shear_y * vb[kz];
+    let cx = vc[kx] - shear_x * vc[kz];
+    let cy = vc[ky] - shear_y * vc[kz];
+
+    // Scaled barycentrics from 2D edge functions
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let determinant = u + v + w;
+    if determinant == 0.0 {
+        return None;
+    }
+
+    let az = shear_z * va[kz];
+    let bz = shear_z * vb[kz];
+    let cz = shear_z * vc[kz];
+    let t = (u * az + v * bz + w * cz) / determinant;
+
+    if !interval.contains(&t) {
+        return None;
+    }
+
+    Some((t, DVec2::new(v / determinant, w / determinant)))
+}
@@ -1,16 +1,16 @@
-use glam::DVec3;
-use rand::{self, Rng};
+use std::f64::consts::PI;
+use glam::{DVec2, DVec3};
+use rand::Rng;
 
 // Supporting functions to work with vectors
 
 // Generate a random unit vector
-pub fn random_unit_vector() -> DVec3 {
-    random_in_unit_sphere().normalize()
+pub fn random_unit_vector(rng: &mut impl Rng) -> DVec3 {
+    random_in_unit_sphere(rng).normalize()
 }
 
 // Generate a random vector in the unit sphere
-pub fn random_in_unit_sphere() -> DVec3 {
-    let mut rng = rand::rng();
+pub fn random_in_unit_sphere(rng: &mut impl Rng) -> DVec3 {
     loop {
         let p = DVec3::new(
             rng.random_range(-1.0..1.0),
@@ -27,8 +27,7 @@ pub fn random_in_unit_sphere() -> DVec3 {
 }
 
 // Generatre a random unit vector on a disk in the x-y plane
-pub fn random_in_unit_disk() -> DVec3 {
-    let mut rng = rand::rng();
+pub fn random_in_unit_disk(rng: &mut impl Rng) -> DVec3 {
     loop {
         let p = DVec3::new(
             rng.random_range(-1.0..1.0),
@@ -45,8 +44,8 @@ pub fn random_in_unit_disk() -> DVec3 {
 }
 
 // Generate a random unit vector in the same hemisphere as another vector
-pub fn random_in_unit_hemisphere(normal: &DVec3) -> DVec3 {
-    let unit_vec = random_unit_vector();
+pub fn random_in_unit_hemisphere(normal: &DVec3, rng: &mut impl Rng) -> DVec3 {
+    let unit_vec = random_unit_vector(rng);
     if unit_vec.dot(*normal) > 0.0 {
         unit_vec
     } else {
@@ -54,6 +53,71 @@ pub fn random_in_unit_hemisphere(normal: &DVec3) -> DVec3 {
     }
 }
 
+// Map a uniform point in [0, 1)^2 to a uniformly distributed unit vector
+pub fn sample_unit_vector(u: DVec2) -> DVec3 {
+    let z = 1.0 - 2.0 * u.x;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    DVec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Map a uniform point in [0, 1)^2 to a uniform point on the unit disk in the x-y plane.
+// Shirley and Chiu's concentric mapping keeps neighbouring samples close together.
+pub fn sample_unit_disk(u: DVec2) -> DVec3 {
+    let offset = 2.0 * u - DVec2::ONE;
+    if offset == DVec2::ZERO {
+        return DVec3::ZERO;
+    }
+
+    let (r, theta) = if offset.x.abs() > offset.y.abs() {
+        (offset.x, PI / 4.0 * (offset.y / offset.x))
+    } else {
+        (offset.y, PI / 2.0 - PI / 4.0 * (offset.x / offset.y))
+    };
+    DVec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+// Map a uniform point in [0, 1)^2 to a direction about +z with density cos(theta) / pi
+pub fn sample_cosine_hemisphere(u: DVec2) -> DVec3 {
+    let disk = sample_unit_disk(u);
+    let z = (1.0 - disk.x * disk.x - disk.y * disk.y).max(0.0).sqrt();
+    DVec3::new(disk.x, disk.y, z)
+}
+
+// Map a uniform point in [0, 1)^2 to a direction about +z inside a cone, uniform over its solid angle
+pub fn sample_cone(u: DVec2, cos_theta_max: f64) -> DVec3 {
+    let z = 1.0 + u.x * (cos_theta_max - 1.0);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.y;
+    DVec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Orthonormal basis with w along a given direction, for turning local samples about +z into world space
+#[derive(Debug, Copy, Clone)]
+pub struct Onb {
+    pub u: DVec3,
+    pub v: DVec3,
+    pub w: DVec3,
+}
+
+impl Onb {
+    // Branchless construction from Duff et al. (2017), `w` must be normalised
+    pub fn new(w: DVec3) -> Onb {
+        let sign = 1.0_f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        Onb {
+            u: DVec3::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x),
+            v: DVec3::new(b, sign + w.y * w.y * a, -w.y),
+            w,
+        }
+    }
+
+    pub fn local(&self, vector: DVec3) -> DVec3 {
+        vector.x * self.u + vector.y * self.v + vector.z * self.w
+    }
+}
+
 // Check for very small vectors
 pub fn near_zero(vector: DVec3) -> bool {{
     let s = 1e-8;